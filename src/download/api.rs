@@ -1,5 +1,249 @@
-use std::error::Error;
-use serde::Deserialize;
+use std::{collections::HashMap, error::Error, fmt, sync::Mutex, time::Duration};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use super::{config::Config, error::DownloadError, redact::{add_secret, redact_error}, retry::{classify, retry, throttle, Attempt, Network}};
+
+
+
+pub const DEFAULT_API_URL: &str = "https://itch.io/api/1";
+
+/// API the itch app uses, the only one with collections.
+pub const DEFAULT_APP_API_URL: &str = "https://api.itch.io";
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"), " (", env!("CARGO_PKG_REPOSITORY"), ")");
+
+/// HTTP client shared by every request, so connections get reused.
+pub fn build_http_client(network: &Network) -> Result<reqwest::Client, Box<dyn Error>> {
+    Ok(reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(network.timeout))
+        .build()?)
+}
+
+/// Download key of each owned game (game id to download key id), per API key, fetched once per run.
+static OWNED_KEYS: Mutex<Option<HashMap<String, HashMap<i64, i64>>>> = Mutex::new(None);
+
+fn with_download_key(url: String, download_key_id: Option<i64>) -> String {
+    match download_key_id {
+        Some(download_key_id) => format!("{}?download_key_id={}", url, download_key_id),
+        None => url,
+    }
+}
+
+/// Error payload itch.io returns instead of the requested data.
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    errors: Vec<String>,
+}
+
+/// Map a failed response to a typed error, `resource` names what was requested.
+fn api_error(status: reqwest::StatusCode, errors: &[String], retry_after: Option<u64>, resource: &str) -> DownloadError {
+    let message = errors.join(", ").to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|word| message.contains(word));
+
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || mentions(&["rate limit", "too many requests"]) {
+        DownloadError::RateLimited(retry_after)
+    } else if status == reqwest::StatusCode::UNAUTHORIZED || mentions(&["invalid key", "invalid api key"]) {
+        DownloadError::InvalidApiKey
+    } else if status == reqwest::StatusCode::FORBIDDEN || mentions(&["not own", "must own", "purchase", "permission", "not allowed"]) {
+        DownloadError::NotOwned(resource.into())
+    } else if status == reqwest::StatusCode::NOT_FOUND || mentions(&["not found", "invalid game", "invalid upload"]) {
+        DownloadError::NotFound(resource.into())
+    } else if errors.is_empty() {
+        DownloadError::Api(resource.into(), status.to_string())
+    } else {
+        DownloadError::Api(resource.into(), errors.join(", "))
+    }
+}
+
+
+
+/// Client for the itch.io API.
+///
+/// The API key is sent in the `Authorization` header, never in the URL,
+/// so it can't leak through error messages or proxy logs.
+#[derive(Clone)]
+pub struct ItchClient {
+    http: reqwest::Client,
+    base_url: String,
+    app_base_url: String,
+    api_key: String,
+    network: Network,
+}
+
+impl fmt::Debug for ItchClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ItchClient")
+            .field("base_url", &self.base_url)
+            .field("app_base_url", &self.app_base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ItchClient {
+
+    pub fn from_parts(http: reqwest::Client, base_url: &str, api_key: String, network: Network) -> Self {
+        add_secret(&api_key);
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').into(),
+            app_base_url: DEFAULT_APP_API_URL.into(),
+            api_key,
+            network,
+        }
+    }
+
+    pub fn with_app_api_url(mut self, app_base_url: &str) -> Self {
+        self.app_base_url = app_base_url.trim_end_matches('/').into();
+        self
+    }
+
+    /// Client using the API key of a profile, `None` is the top level key.
+    pub async fn new(config: &Config, profile: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let api_key = config.api_key_for(profile).await?;
+        Ok(Self::from_parts(config.http_client()?, config.api_url(), api_key, config.network.clone()).with_app_api_url(config.app_api_url()))
+    }
+
+    /// Client without an API key, only for public pages.
+    pub fn anonymous(config: &Config) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_parts(config.http_client()?, config.api_url(), String::new(), config.network.clone()).with_app_api_url(config.app_api_url()))
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// `key` in place of the API key tells itch.io to read it from the `Authorization` header.
+    fn api_url(&self, path: &str) -> String {
+        format!("{}/key/{}", self.base_url, path)
+    }
+
+    /// GET `url`, retrying transient failures.
+    async fn get<T: DeserializeOwned>(&self, url: &str, resource: &str) -> Result<T, Box<dyn Error>> {
+        let body = self.get_text(url, resource).await?;
+        match serde_json::from_str::<T>(&body) {
+            Ok(value) => Ok(value),
+            Err(err) => Err(Box::new(DownloadError::Api(resource.into(), format!("unexpected response, {}", err)))),
+        }
+    }
+
+    /// GET the body of `url`, retrying transient failures.
+    pub async fn get_text(&self, url: &str, resource: &str) -> Result<String, Box<dyn Error>> {
+        retry(&self.network, &format!("Request for {}", resource), || self.get_once(url, resource)).await
+    }
+
+    /// itch.io reports some errors with a success status, so the body is always checked for an error envelope.
+    async fn get_once(&self, url: &str, resource: &str) -> Result<String, Attempt> {
+        throttle(&self.network).await;
+
+        let mut request = self.http.get(url).timeout(Duration::from_secs(self.network.timeout));
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(&self.api_key);
+        }
+        let response = request.send().await.map_err(|err| classify(Box::new(redact_error(err))))?;
+
+        let status = response.status();
+        let retry_after = response.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        let body = response.text().await.map_err(|err| classify(Box::new(redact_error(err))))?;
+
+        let errors = serde_json::from_str::<ErrorEnvelope>(&body).map(|envelope| envelope.errors).unwrap_or_default();
+        if !status.is_success() || !errors.is_empty() {
+            let err = Box::new(api_error(status, &errors, retry_after, resource));
+            return Err(if status.is_server_error() { Attempt::Retry(err, None) } else { classify(err) });
+        }
+
+        Ok(body)
+    }
+
+    pub async fn game_info(&self, game_id: &i64) -> Result<GameInfo, Box<dyn Error>> {
+        self.get(&self.api_url(&format!("game/{}", game_id)), &format!("game {}", game_id)).await
+    }
+
+    /// Uploads of a game, paid games need the `download_key_id` of a purchase.
+    pub async fn game_uploads(&self, game_id: &i64, download_key_id: Option<i64>) -> Result<GameUploads, Box<dyn Error>> {
+        let url = with_download_key(self.api_url(&format!("game/{}/uploads", game_id)), download_key_id);
+        self.get(&url, &format!("uploads of game {}", game_id)).await
+    }
+
+    pub async fn upload_download(&self, upload_id: &i64, download_key_id: Option<i64>) -> Result<UploadDownload, Box<dyn Error>> {
+        let url = with_download_key(self.api_url(&format!("upload/{}/download", upload_id)), download_key_id);
+        self.get(&url, &format!("upload {}", upload_id)).await
+    }
+
+    /// Every game bought or claimed with the account.
+    pub async fn owned_keys(&self) -> Result<Vec<OwnedKey>, Box<dyn Error>> {
+        let mut owned_keys = Vec::new();
+        for page in 1.. {
+            let response: OwnedKeys = self.get(&self.api_url(&format!("my-owned-keys?page={}", page)), "owned games").await?;
+            let last_page = response.owned_keys.is_empty() || response.per_page.is_some_and(|per_page| (response.owned_keys.len() as i64) < per_page);
+            owned_keys.extend(response.owned_keys);
+            if last_page {
+                break;
+            }
+        }
+        Ok(owned_keys)
+    }
+
+    /// Download key of a game the account owns, `None` for free games & games it doesn't own.
+    pub async fn download_key_id(&self, game_id: &i64) -> Result<Option<i64>, Box<dyn Error>> {
+        if let Some(keys) = OWNED_KEYS.lock().unwrap().as_ref().and_then(|cache| cache.get(&self.api_key)) {
+            return Ok(keys.get(game_id).copied());
+        }
+
+        let keys = self.owned_keys().await?
+            .into_iter()
+            .map(|owned_key| (owned_key.game_id, owned_key.id))
+            .collect::<HashMap<i64, i64>>();
+        let download_key_id = keys.get(game_id).copied();
+        OWNED_KEYS.lock().unwrap().get_or_insert_with(HashMap::new).insert(self.api_key.clone(), keys);
+
+        Ok(download_key_id)
+    }
+
+    pub async fn collection(&self, collection_id: &i64) -> Result<CollectionInfo, Box<dyn Error>> {
+        self.get(&format!("{}/collections/{}", self.app_base_url, collection_id), &format!("collection {}", collection_id)).await
+    }
+
+    /// Every game in a collection, in collection order.
+    pub async fn collection_games(&self, collection_id: &i64) -> Result<Vec<CollectionGame>, Box<dyn Error>> {
+        let mut collection_games = Vec::new();
+        for page in 1.. {
+            let url = format!("{}/collections/{}/collection-games?page={}", self.app_base_url, collection_id, page);
+            let response: CollectionGames = self.get(&url, &format!("collection {}", collection_id)).await?;
+            let last_page = response.collection_games.is_empty() || response.per_page.is_some_and(|per_page| (response.collection_games.len() as i64) < per_page);
+            collection_games.extend(response.collection_games);
+            if last_page {
+                break;
+            }
+        }
+        Ok(collection_games)
+    }
+
+    /// Entries of a jam, from the `entries.json` its entries page uses.
+    pub async fn jam_entries(&self, entries_url: &str) -> Result<JamEntries, Box<dyn Error>> {
+        self.get(entries_url, "jam entries").await
+    }
+
+    /// The user the API key belongs to, used to check the key is valid.
+    pub async fn me(&self) -> Result<Me, Box<dyn Error>> {
+        self.get(&self.api_url("me"), "API key owner").await
+    }
+
+    /// `data.json` of a game page.
+    pub async fn page_data(&self, page_url: &str) -> Result<PageData, Box<dyn Error>> {
+        let mut url = reqwest::Url::parse(page_url)?;
+        url.set_query(None);
+        url.set_fragment(None);
+        self.get(&format!("{}/data.json", url.as_str().trim_end_matches('/')), &format!("page {}", url)).await
+    }
+
+}
 
 
 
@@ -8,7 +252,15 @@ pub struct GameInfo {
     pub game: GameInfoGame,
 }
 
-#[derive(Deserialize)]
+fn platforms(windows: bool, linux: bool, osx: bool, android: bool) -> Vec<&'static str> {
+    [("windows", windows), ("linux", linux), ("osx", osx), ("android", android)]
+        .into_iter()
+        .filter(|(_, supported)| *supported)
+        .map(|(platform, _)| platform)
+        .collect()
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct GameInfoGame {
     pub can_be_bought: bool,
     pub has_demo: bool,
@@ -30,14 +282,7 @@ pub struct GameInfoGame {
     pub short_text: Option<String>,
 }
 
-#[derive(Deserialize)]
-pub struct GameEmbed {
-    pub width: i64,
-    pub height: i64,
-    pub fullscreen: bool,
-}
-
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct GameUser {
     pub username: String,
     pub url: String,
@@ -45,9 +290,10 @@ pub struct GameUser {
     pub cover_url: Option<String>,
 }
 
-pub async fn itch_api_game_info(api_key: &str, game_id: &i64) -> Result<GameInfo, Box<dyn Error>> {
-    let url = format!("https://itch.io/api/1/{}/game/{}", api_key, game_id);
-    Ok(reqwest::get(url).await?.json::<GameInfo>().await?)
+impl GameInfoGame {
+    pub fn platforms(&self) -> Vec<&'static str> {
+        platforms(self.p_windows, self.p_linux, self.p_osx, self.p_android)
+    }
 }
 
 
@@ -57,7 +303,7 @@ pub struct GameUploads {
     pub uploads: Vec<GameUpload>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct GameUpload {
     pub id: i64,
     pub build_id: Option<i64>,
@@ -87,7 +333,7 @@ pub struct GameUpload {
     pub display_name: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct GameUploadBuild {
     pub id: i64,
     pub parent_build_id: i64,
@@ -100,9 +346,14 @@ pub struct GameUploadBuild {
     pub user_version: Option<String>,
 }
 
-pub async fn itch_api_game_uploads(api_key: &str, game_id: &i64) -> Result<GameUploads, Box<dyn Error>> {
-    let url = format!("https://itch.io/api/1/{}/game/{}/uploads", api_key, game_id);
-    Ok(reqwest::get(url).await?.json::<GameUploads>().await?)
+impl GameUpload {
+    pub fn platforms(&self) -> Vec<&'static str> {
+        platforms(self.p_windows, self.p_linux, self.p_osx, self.p_android)
+    }
+
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.filename)
+    }
 }
 
 
@@ -112,9 +363,95 @@ pub struct UploadDownload {
     pub url: String,
 }
 
-pub async fn itch_api_upload_download(api_key: &str, upload_id: &i64) -> Result<UploadDownload, Box<dyn Error>> {
-    let url = format!("https://itch.io/api/1/{}/upload/{}/download", api_key, upload_id);
-    Ok(reqwest::get(url).await?.json::<UploadDownload>().await?)
+
+
+#[derive(Deserialize)]
+pub struct Me {
+    pub user: GameUser,
+}
+
+
+
+#[derive(Deserialize)]
+pub struct OwnedKeys {
+    pub owned_keys: Vec<OwnedKey>,
+    pub per_page: Option<i64>,
+}
+
+/// A purchase (or claim) of a game, its id is the `download_key_id` paid games need.
+#[derive(Deserialize, Serialize)]
+pub struct OwnedKey {
+    pub id: i64,
+    pub game_id: i64,
+    pub created_at: String,
+    pub game: OwnedKeyGame,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct OwnedKeyGame {
+    pub id: i64,
+    pub title: String,
+    pub url: String,
+    pub short_text: Option<String>,
+}
+
+
+
+#[derive(Deserialize)]
+pub struct CollectionInfo {
+    pub collection: Collection,
+}
+
+#[derive(Deserialize)]
+pub struct Collection {
+    pub id: i64,
+    pub title: String,
+}
+
+#[derive(Deserialize)]
+pub struct CollectionGames {
+    pub collection_games: Vec<CollectionGame>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CollectionGame {
+    pub game: CollectionGameGame,
+}
+
+#[derive(Deserialize)]
+pub struct CollectionGameGame {
+    pub id: i64,
+    pub title: String,
+}
+
+
+
+#[derive(Deserialize)]
+pub struct JamEntries {
+    pub jam_games: Vec<JamGame>,
+}
+
+#[derive(Deserialize)]
+pub struct JamGame {
+    pub game: JamGameGame,
+}
+
+#[derive(Deserialize)]
+pub struct JamGameGame {
+    pub id: i64,
+    pub title: String,
+    /// Platforms with a download, `web` for browser games.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+}
+
+
+
+/// Game page `data.json`, what the browser addon uses to find the game id.
+#[derive(Deserialize)]
+pub struct PageData {
+    pub id: i64,
 }
 
 
@@ -1,49 +1,596 @@
 
-use std::{error::Error, path::PathBuf};
+use std::{collections::{BTreeMap, HashMap}, error::Error, fmt, path::{Path, PathBuf}, sync::{Arc, Mutex, OnceLock}};
+use console::style;
 use serde::{Deserialize, Serialize};
-use tokio::fs;
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+use super::{api::{build_http_client, DEFAULT_API_URL, DEFAULT_APP_API_URL}, error::DownloadError, policy::{Platform, UploadRule}, prompt::PromptMode, redact::{add_secret, redact_key}, retry::Network};
 
 
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// An itch.io account, for when several accounts share one machine.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub api_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_command: Option<String>,
+    /// Games directory for this profile, instead of the top level `games_dir`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub games_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Config {
+    /// Schema version, older files are migrated on load.
+    #[serde(default)]
+    pub version: u32,
     pub games_dir: PathBuf,
+    /// Overridden by the `ITCH_API_KEY` environment variable, which doesn't apply to profiles with their own key.
+    #[serde(default)]
     pub api_key: String,
+    /// Command that prints the API key, used instead of `api_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_command: Option<String>,
+    /// How install & update prompts get answered.
+    #[serde(default)]
+    pub prompt: PromptMode,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Profile used when `--profile` isn't given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    /// Base URL of the itch.io API, only changed for testing against a mock server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    /// Base URL of the itch app API, used for collections.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_api_url: Option<String>,
+    /// Command that runs windows games on other platforms, like `wine`.
+    /// When set, windows uploads are installed for games without a native upload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub windows_runner: Option<String>,
+    /// Rules that steer which upload of a game gets installed, like preferring channels matching `*-64`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub upload_rules: Vec<UploadRule>,
+    /// Retries, timeouts & request rate.
+    #[serde(default)]
+    pub network: Network,
+    /// Profile in use, `None` uses the top level `api_key` & `games_dir`.
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// Platform to install uploads for (`--platform`), instead of the host platform.
+    #[serde(skip)]
+    pub platform: Option<Platform>,
+    /// File the config was loaded from.
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Output of each `api_key_command`, shared between clones so each only runs once.
+    #[serde(skip)]
+    api_key_resolved: Arc<Mutex<HashMap<String, String>>>,
+    /// HTTP client shared between clones, so connections get reused.
+    #[serde(skip)]
+    http_client: Arc<OnceLock<reqwest::Client>>,
+}
+
+// API keys only show their last 4 characters, resolved `api_key_command` output is left out entirely.
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Profile")
+            .field("api_key", &redact_key(&self.api_key))
+            .field("api_key_command", &self.api_key_command)
+            .field("games_dir", &self.games_dir)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("version", &self.version)
+            .field("games_dir", &self.games_dir)
+            .field("api_key", &redact_key(&self.api_key))
+            .field("api_key_command", &self.api_key_command)
+            .field("prompt", &self.prompt)
+            .field("profiles", &self.profiles)
+            .field("default_profile", &self.default_profile)
+            .field("api_url", &self.api_url)
+            .field("app_api_url", &self.app_api_url)
+            .field("windows_runner", &self.windows_runner)
+            .field("upload_rules", &self.upload_rules)
+            .field("network", &self.network)
+            .field("profile", &self.profile)
+            .field("platform", &self.platform)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Every key a config file can have.
+pub const CONFIG_KEYS: &[&str] = &["version", "games_dir", "api_key", "api_key_command", "prompt", "profiles", "default_profile", "api_url", "app_api_url", "windows_runner", "upload_rules", "network"];
+
+/// Every key a profile can have.
+const PROFILE_KEYS: &[&str] = &["api_key", "api_key_command", "games_dir"];
+
+/// Value of `api_key` in `itch-io-downloader.json.placeholder`.
+const API_KEY_PLACEHOLDER: &str = "API_KEY_HERE";
+
+type Migration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+/// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+const MIGRATIONS: &[Migration] = &[
+    // 0 -> 1: Unversioned config, drop the placeholder API key so it reads as unset.
+    |config| {
+        if config.get("api_key").and_then(|v| v.as_str()) == Some(API_KEY_PLACEHOLDER) {
+            // Keeps the order of the other keys, `remove` would move the last key into this slot.
+            config.shift_remove("api_key");
+        }
+    },
+];
+
+/// Version written by this build.
+pub const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// 1-based line of the first `"key":` in `text`.
+fn key_line(text: &str, key: &str) -> Option<usize> {
+    let needle = format!("\"{}\"", key);
+    text.lines()
+        .position(|line| line.find(&needle).is_some_and(|i| line[i + needle.len()..].trim_start().starts_with(':')))
+        .map(|line| line + 1)
+}
+
+/// Print a warning for every key this build doesn't know about.
+fn warn_unknown_keys(path: &Path, text: &str, value: &serde_json::Value) {
+    let warn = |key: &str, name: &str| {
+        let location = match key_line(text, key) {
+            Some(line) => format!("{}:{}", path.display(), line),
+            None => path.display().to_string(),
+        };
+        eprintln!("{} {}: unknown config key \"{}\", it is ignored", style("Warning:").yellow().bold(), location, name);
+    };
+
+    let Some(object) = value.as_object() else { return };
+    for key in object.keys().filter(|key| !CONFIG_KEYS.contains(&key.as_str())) {
+        warn(key, key);
+    }
+    if let Some(profiles) = object.get("profiles").and_then(|profiles| profiles.as_object()) {
+        for (name, profile) in profiles {
+            let Some(profile) = profile.as_object() else { continue };
+            for key in profile.keys().filter(|key| !PROFILE_KEYS.contains(&key.as_str())) {
+                warn(key, &format!("profiles.{}.{}", name, key));
+            }
+        }
+    }
+}
+
+/// Parse a config file, reporting the file, line & key of any error.
+fn parse(path: &Path, text: &str) -> Result<Config, Box<dyn Error>> {
+    let deserializer = &mut serde_json::Deserializer::from_str(text);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let key = err.path().to_string();
+        let inner = err.into_inner();
+        let line = match key.rsplit('.').next() {
+            // Missing fields are reported at the end of the file, point at the object instead.
+            Some(name) if inner.to_string().starts_with("missing field") => key_line(text, name).unwrap_or(inner.line()),
+            _ => inner.line(),
+        };
+        Box::new(DownloadError::ConfigInvalid {
+            path: path.to_path_buf(),
+            line,
+            // Syntax errors have no key, their path is "?".
+            key: if key == "." || inner.is_syntax() || inner.is_eof() { None } else { Some(key) },
+            message: inner.to_string().split(" at line ").next().unwrap_or_default().to_string(),
+        }) as Box<dyn Error>
+    })
+}
+
+/// Upgrade an older config file in place, keeping a `.bak` copy of the original.
+async fn migrate(path: &Path, text: String) -> Result<String, Box<dyn Error>> {
+    let mut value: serde_json::Value = match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(err) => return Err(Box::new(DownloadError::ConfigInvalid {
+            path: path.to_path_buf(),
+            line: err.line(),
+            key: None,
+            message: err.to_string().split(" at line ").next().unwrap_or_default().to_string(),
+        })),
+    };
+
+    let Some(object) = value.as_object_mut() else { return Ok(text) };
+    let version = object.get("version").and_then(|version| version.as_u64()).unwrap_or(0) as u32;
+
+    if version > CONFIG_VERSION {
+        return Err(Box::new(DownloadError::ConfigTooNew(path.to_path_buf(), version)));
+    }
+    if version == CONFIG_VERSION {
+        return Ok(text);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    object.insert("version".into(), CONFIG_VERSION.into());
+
+    // A read-only config still loads, it just gets migrated again every run.
+    let backup_path = path.with_extension("json.bak");
+    let written = match fs::copy(path, &backup_path).await {
+        Ok(_) => write_file(path, &value).await,
+        Err(err) => Err(Box::new(err) as Box<dyn Error>),
+    };
+    match written {
+        Ok(()) => {
+            eprintln!("{} {} {} {}", style("Upgraded config").magenta(), style(path.display()).cyan(), style("to version").magenta(), style(CONFIG_VERSION).cyan());
+            eprintln!("{} {}", style("Original kept at").magenta(), style(backup_path.display()).cyan());
+        },
+        Err(err) => {
+            eprintln!("{} couldn't upgrade {} to version {} ({}), using the upgraded config without saving it", style("Warning:").yellow().bold(), path.display(), CONFIG_VERSION, err);
+        },
+    }
+
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// Set a single key in a config file, leaving every other key untouched.
+pub async fn write_file_value(path: &Path, key: &str, value: serde_json::Value) -> Result<(), Box<dyn Error>> {
+    let mut file_value: serde_json::Value = match fs::read_to_string(path).await {
+        Ok(str) => serde_json::from_str(&str)?,
+        Err(err) if err.kind() == tokio::io::ErrorKind::NotFound => serde_json::Value::Object(Default::default()),
+        Err(err) => return Err(Box::new(err)),
+    };
+    if let Some(object) = file_value.as_object_mut() {
+        object.insert(key.into(), value);
+    }
+    write_file(path, &file_value).await
 }
 
+/// Write a whole config file.
+pub async fn write_file(path: &Path, value: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    // Write to a temporary file first so an interrupted write can't corrupt the config.
+    let temp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&temp_path).await?;
+    file.write_all(serde_json::to_string_pretty(value)?.as_bytes()).await?;
+    file.flush().await?;
+    drop(file);
+    fs::rename(&temp_path, path).await?;
+
+    Ok(())
+}
+
+
+
 impl Config {
 
-    fn base_dir() -> Result<PathBuf, Box<dyn Error>> {
-        let mut base_dir = std::env::current_exe()?;
-        base_dir.pop();
+    /// Directories a portable config can sit in, next to the executable.
+    /// Debug builds also look in the crate root so `cargo run` works.
+    fn portable_dirs() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut exe_dir = std::env::current_exe()?;
+        exe_dir.pop();
+
+        let mut dirs = vec![ exe_dir ];
         if cfg!(debug_assertions) {
-            base_dir.pop();
-            base_dir.pop();
+            dirs.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")));
         }
-        Ok(base_dir)
+        Ok(dirs)
+    }
+
+    /// Per-user config directory, `$XDG_CONFIG_HOME` (or `~/.config`) on Linux.
+    fn user_config_dir() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            PathBuf::from(std::env::var_os("APPDATA")?)
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from(std::env::var_os("HOME")?).join("Library").join("Application Support")
+        } else {
+            match std::env::var_os("XDG_CONFIG_HOME") {
+                Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+                _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+            }
+        };
+        Some(dir.join("itch-io-downloader").join("config.json"))
+    }
+
+    /// Candidate config files in search order, the first that exists is used.
+    pub fn search_paths() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut paths = Vec::new();
+        if let Some(path) = Self::user_config_dir() {
+            paths.push(path);
+        }
+        for dir in Self::portable_dirs()? {
+            paths.push(dir.join("itch-io-downloader.json"));
+        }
+        Ok(paths)
     }
 
     pub async fn load_from_file(file: PathBuf) -> Result<Self, Box<dyn Error>> {
-        let mut config: Config = serde_json::from_str(&fs::read_to_string(file).await?)?;
+        let text = migrate(&file, fs::read_to_string(&file).await?).await?;
+        if let Ok(value) = serde_json::from_str(&text) {
+            warn_unknown_keys(&file, &text, &value);
+        }
+        let mut config = parse(&file, &text)?;
 
-        // Fix config paths
-        if !config.games_dir.has_root() {
-            let mut new_games_dir = Self::base_dir()?;
-            new_games_dir.push(&config.games_dir);
-            config.games_dir = new_games_dir;
+        // Relative paths are relative to the config file.
+        let base_dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        config.games_dir = base_dir.join(&config.games_dir);
+        for profile in config.profiles.values_mut() {
+            if let Some(games_dir) = &profile.games_dir {
+                profile.games_dir = Some(base_dir.join(games_dir));
+            }
+            add_secret(&profile.api_key);
         }
+        add_secret(&config.api_key);
+
+        config.path = file;
 
         Ok(config)
     }
 
-    pub async fn load() -> Result<Self, Box<dyn Error>> {
-        // Locate the config file.
-        let mut config_location = Self::base_dir()?;
-        config_location.push("itch-io-downloader.json");
+    /// Locate the config file, in order: `explicit` (`--config`), `ITCH_IO_DOWNLOADER_CONFIG`,
+    /// the user config directory, then a portable config next to the executable.
+    pub async fn locate(explicit: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+        if let Some(path) = explicit {
+            return Ok(path.to_path_buf());
+        }
+
+        if let Some(path) = std::env::var_os("ITCH_IO_DOWNLOADER_CONFIG") {
+            if !path.is_empty() {
+                return Ok(PathBuf::from(path));
+            }
+        }
+
+        let paths = Self::search_paths()?;
+        for path in &paths {
+            if fs::try_exists(path).await? {
+                return Ok(path.clone());
+            }
+        }
+
+        Err(Box::new(DownloadError::ConfigNotFound(paths)))
+    }
+
+    /// Switch to a profile, `None` uses `default_profile` if set.
+    pub fn select_profile(&mut self, profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let profile = match profile.map(String::from).or(self.default_profile.clone()) {
+            Some(profile) => profile,
+            None => return Ok(()),
+        };
 
-        Ok(Self::load_from_file(config_location).await?)
+        match self.profiles.get(&profile) {
+            Some(Profile { games_dir: Some(games_dir), .. }) => self.games_dir = games_dir.clone(),
+            Some(_) => { },
+            None => return Err(Box::new(DownloadError::ProfileNotFound(profile))),
+        }
+
+        self.profile = Some(profile);
+        Ok(())
+    }
+
+    /// Where a new config file gets created, `explicit` or `ITCH_IO_DOWNLOADER_CONFIG`,
+    /// otherwise the user config directory.
+    pub async fn default_path(explicit: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+        match Self::locate(explicit).await {
+            Ok(path) => Ok(path),
+            Err(_) => match Self::user_config_dir() {
+                Some(path) => Ok(path),
+                None => Ok(Self::portable_dirs()?[0].join("itch-io-downloader.json")),
+            },
+        }
+    }
+
+    pub async fn load(explicit: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let path = Self::locate(explicit).await?;
+        if !fs::try_exists(&path).await? {
+            return Err(Box::new(DownloadError::ConfigNotFound(vec![ path ])));
+        }
+        Self::load_from_file(path).await
+    }
+
+    /// Set a single key in the config file, leaving every other key untouched.
+    pub async fn set_file_value(&self, key: &str, value: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        write_file_value(&self.path, key, value).await
+    }
+
+    /// Point the config at a moved library, the active profile's `games_dir` if it has its own, otherwise the top level one.
+    pub async fn set_games_dir(&self, games_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let value = serde_json::to_value(games_dir)?;
+        let profile = self.profile.as_ref()
+            .filter(|name| self.profiles.get(*name).is_some_and(|profile| profile.games_dir.is_some()));
+
+        match profile {
+            Some(name) => {
+                let mut file_value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&self.path).await?)?;
+                file_value["profiles"][name]["games_dir"] = value;
+                write_file(&self.path, &file_value).await
+            },
+            None => self.set_file_value("games_dir", value).await,
+        }
+    }
+
+    async fn run_api_key_command(command: &str) -> Result<String, Box<dyn Error>> {
+        let output = if cfg!(windows) {
+            Command::new("cmd").arg("/C").arg(command).output().await
+        } else {
+            Command::new("sh").arg("-c").arg(command).output().await
+        };
+
+        let output = match output {
+            Ok(output) => output,
+            Err(err) => return Err(Box::new(DownloadError::ApiKeyCommandFailed(command.into(), err.to_string()))),
+        };
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(Box::new(DownloadError::ApiKeyCommandFailed(command.into(), format!("{}, {}", output.status, stderr))));
+        }
+
+        let api_key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if api_key.is_empty() {
+            return Err(Box::new(DownloadError::ApiKeyCommandFailed(command.into(), "printed nothing".into())));
+        }
+
+        Ok(api_key)
+    }
+
+    pub fn api_url(&self) -> &str {
+        self.api_url.as_deref().unwrap_or(DEFAULT_API_URL)
+    }
+
+    pub fn app_api_url(&self) -> &str {
+        self.app_api_url.as_deref().unwrap_or(DEFAULT_APP_API_URL)
+    }
+
+    pub fn http_client(&self) -> Result<reqwest::Client, Box<dyn Error>> {
+        if let Some(client) = self.http_client.get() {
+            return Ok(client.clone());
+        }
+        let client = build_http_client(&self.network)?;
+        Ok(self.http_client.get_or_init(|| client).clone())
+    }
+
+    /// The API key of a profile, `None` is the top level key. Profiles without a key of their own use the top level key.
+    /// In order: `ITCH_API_KEY` (top level key only), then `api_key_command` (run at most once) or `api_key`.
+    pub async fn api_key_for(&self, profile: Option<&str>) -> Result<String, Box<dyn Error>> {
+        let own_key = match profile {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) if !profile.api_key.is_empty() || profile.api_key_command.is_some() => Some(profile),
+                Some(_) => None,
+                None => return Err(Box::new(DownloadError::ProfileNotFound(name.into()))),
+            },
+            None => None,
+        };
+
+        // The environment only stands in for the top level key, games of a profile stay with its account.
+        let (api_key, api_key_command) = match own_key {
+            Some(profile) => (&profile.api_key, &profile.api_key_command),
+            None => {
+                if let Ok(api_key) = std::env::var("ITCH_API_KEY") {
+                    if !api_key.is_empty() {
+                        return Ok(api_key);
+                    }
+                }
+                (&self.api_key, &self.api_key_command)
+            },
+        };
+
+        let has_api_key = !api_key.is_empty() && api_key != API_KEY_PLACEHOLDER;
+
+        match api_key_command {
+            Some(_) if has_api_key => Err(Box::new(DownloadError::ApiKeyConflict(self.path.clone()))),
+            Some(command) => {
+                if let Some(api_key) = self.api_key_resolved.lock().unwrap().get(command) {
+                    return Ok(api_key.clone());
+                }
+                let api_key = Self::run_api_key_command(command).await?;
+                self.api_key_resolved.lock().unwrap().insert(command.clone(), api_key.clone());
+                Ok(api_key)
+            },
+            None if has_api_key => Ok(api_key.clone()),
+            None => Err(Box::new(DownloadError::ApiKeyMissing(self.path.clone()))),
+        }
     }
 
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory for a test's config files.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("itch-io-downloader-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn invalid(err: Box<dyn Error>) -> (usize, Option<String>, String) {
+        match err.downcast_ref::<DownloadError>() {
+            Some(DownloadError::ConfigInvalid { line, key, message, .. }) => (*line, key.clone(), message.clone()),
+            _ => panic!("not a ConfigInvalid error: {}", err),
+        }
+    }
+
+    #[test]
+    fn key_line_finds_keys_not_values() {
+        let text = "{\n    \"games_dir\": \"api_key\",\n    \"api_key\" : \"x\"\n}";
+        assert_eq!(key_line(text, "games_dir"), Some(2));
+        assert_eq!(key_line(text, "api_key"), Some(3));
+        assert_eq!(key_line(text, "prompt"), None);
+    }
+
+    #[test]
+    fn parse_reports_line_and_key_of_invalid_values() {
+        let text = "{\n    \"games_dir\": \"games\",\n    \"prompt\": \"maybe\"\n}";
+        let (line, key, message) = invalid(parse(Path::new("c.json"), text).unwrap_err());
+        assert_eq!((line, key.as_deref()), (3, Some("prompt")));
+        assert!(message.starts_with("unknown variant `maybe`"), "{}", message);
+    }
+
+    #[test]
+    fn parse_reports_line_of_syntax_errors() {
+        let text = "{\n    \"games_dir\": \"games\"\n    \"prompt\": \"ask\"\n}";
+        let (line, key, _) = invalid(parse(Path::new("c.json"), text).unwrap_err());
+        assert_eq!((line, key), (3, None));
+    }
+
+    #[test]
+    fn parse_reports_missing_fields() {
+        let (_, _, message) = invalid(parse(Path::new("c.json"), "{\n}").unwrap_err());
+        assert_eq!(message, "missing field `games_dir`");
+    }
+
+    #[tokio::test]
+    async fn migrate_keeps_key_order_and_backs_up() {
+        let dir = test_dir("migrate");
+        let path = dir.join("c.json");
+        let original = r#"{"api_key": "API_KEY_HERE", "games_dir": "games", "bogus": 1}"#;
+        std::fs::write(&path, original).unwrap();
+
+        let text = migrate(&path, original.into()).await.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let keys = value.as_object().unwrap().keys().cloned().collect::<Vec<String>>();
+        assert_eq!(keys, vec![ "games_dir", "bogus", "version" ]);
+        assert_eq!(std::fs::read_to_string(dir.join("c.json.bak")).unwrap(), original);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrate_leaves_current_config_alone() {
+        let dir = test_dir("current");
+        let path = dir.join("c.json");
+        let original = format!(r#"{{"version": {}, "games_dir": "games"}}"#, CONFIG_VERSION);
+
+        assert_eq!(migrate(&path, original.clone()).await.unwrap(), original);
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrate_refuses_newer_config() {
+        let original = format!(r#"{{"version": {}, "games_dir": "games"}}"#, CONFIG_VERSION + 1);
+        let err = migrate(Path::new("c.json"), original).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<DownloadError>(), Some(DownloadError::ConfigTooNew(_, version)) if *version == CONFIG_VERSION + 1));
+    }
+
+    #[tokio::test]
+    async fn migrate_keeps_going_when_it_cant_write() {
+        let dir = test_dir("readonly");
+        let path = dir.join("c.json");
+        let original = r#"{"api_key": "API_KEY_HERE", "games_dir": "games"}"#;
+        std::fs::write(&path, original).unwrap();
+        // The backup can't be written over a directory.
+        std::fs::create_dir(dir.join("c.json.bak")).unwrap();
+
+        let text = migrate(&path, original.into()).await.unwrap();
+        assert!(parse(&path, &text).unwrap().api_key.is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+
+use std::{error::Error, path::Path};
+use console::style;
+use tokio::fs;
+use super::{api::{build_http_client, ItchClient, DEFAULT_API_URL}, config::{write_file, Config, CONFIG_KEYS, CONFIG_VERSION}, error::DownloadError, prompt::{self, PromptMode}, redact::redact_key, retry::Network};
+
+
+
+fn check_key(key: &str) -> Result<(), Box<dyn Error>> {
+    if CONFIG_KEYS.contains(&key) {
+        Ok(())
+    } else {
+        Err(Box::new(DownloadError::ConfigInvalidKey(key.into())))
+    }
+}
+
+async fn read_file(path: &Path) -> Result<serde_json::Value, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path).await?)?)
+}
+
+
+
+/// Interactively create a config file, checking the API key before it is written.
+pub async fn config_init(explicit: Option<&Path>, mode: PromptMode, games_dir: Option<String>, api_key: Option<String>) -> Result<(), Box<dyn Error>> {
+    let path = Config::default_path(explicit).await?;
+
+    if fs::try_exists(&path).await? && !prompt::confirm(mode, &format!("{} already exists, do you want to overwrite it?", path.display()))? {
+        return Ok(());
+    }
+
+    println!("{} {}", style("Creating config").magenta(), style(path.display()).cyan());
+
+    let games_dir = match games_dir {
+        Some(games_dir) => games_dir,
+        None => prompt::input(mode, "Games directory (relative to the config file)", Some("games"))?,
+    };
+
+    let api_key = match api_key {
+        Some(api_key) => api_key,
+        None => {
+            println!("{}", style("Create an API key at https://itch.io/user/settings/api-keys").magenta());
+            prompt::password(mode, "itch.io API key")?
+        },
+    };
+    let api_key = api_key.trim().to_string();
+
+    println!("{}", style("Checking API key").magenta());
+    let client = ItchClient::from_parts(build_http_client(&Network::default())?, DEFAULT_API_URL, api_key.clone(), Network::default());
+    let me = client.me().await?;
+    println!("{} {}", style("API key belongs to").magenta(), style(&me.user.username).magenta().bold());
+
+    // Replaces the whole file, keys of an old config like `api_key_command` would conflict with the new key.
+    write_file(&path, &serde_json::json!({
+        "version": CONFIG_VERSION,
+        "games_dir": games_dir,
+        "api_key": api_key,
+    })).await?;
+
+    println!("{} {}", style("Wrote config").magenta(), style(path.display()).cyan());
+
+    Ok(())
+}
+
+pub async fn config_get(explicit: Option<&Path>, key: &str) -> Result<(), Box<dyn Error>> {
+    check_key(key)?;
+    let path = Config::locate(explicit).await?;
+
+    match read_file(&path).await?.get(key) {
+        Some(serde_json::Value::String(value)) => println!("{}", value),
+        Some(value) => println!("{}", value),
+        None => { },
+    }
+
+    Ok(())
+}
+
+pub async fn config_set(explicit: Option<&Path>, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    check_key(key)?;
+    let path = Config::locate(explicit).await?;
+
+    // Use the value as JSON if it is valid JSON, otherwise as a string.
+    let value = serde_json::from_str(value).unwrap_or(serde_json::Value::String(value.into()));
+
+    // Make sure the config is still valid before writing it.
+    let mut file_value = read_file(&path).await?;
+    if let Some(object) = file_value.as_object_mut() {
+        object.insert(key.into(), value);
+    }
+    serde_json::from_value::<Config>(file_value.clone())?;
+
+    write_file(&path, &file_value).await?;
+    Ok(())
+}
+
+pub async fn config_show(config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut value = serde_json::to_value(config)?;
+    if let Some(object) = value.as_object_mut() {
+        object.insert("api_key".into(), serde_json::Value::String(redact_key(&config.api_key)));
+    }
+    for (name, profile) in &config.profiles {
+        if let Some(object) = value["profiles"][name].as_object_mut() {
+            if !profile.api_key.is_empty() {
+                object.insert("api_key".into(), serde_json::Value::String(redact_key(&profile.api_key)));
+            }
+        }
+    }
+
+    println!("{} {}", style("Config file").magenta(), style(config.path.display()).cyan());
+    if let Some(profile) = &config.profile {
+        println!("{} {}", style("Profile").magenta(), style(profile).cyan());
+    }
+    println!("{}", serde_json::to_string_pretty(&value)?);
+
+    Ok(())
+}
+
+
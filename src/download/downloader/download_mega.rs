@@ -6,7 +6,7 @@ use async_read_progress::AsyncReadProgressExt;
 
 
 
-pub async fn download_mega<U, F>(url: U, output: &PathBuf, on_progress: F) -> Result<(), Box<dyn Error>>
+pub async fn download_mega<U, F>(http_client: &reqwest::Client, url: U, output: &PathBuf, on_progress: F) -> Result<(), Box<dyn Error>>
 where
     U: IntoUrl,
     F: Fn(u64, u64)
@@ -17,8 +17,7 @@ where
         panic!("Mega folder download not supported.");
     }
 
-    let http_client = reqwest::Client::new();
-    let mega = mega::Client::builder().build(http_client)?;
+    let mega = mega::Client::builder().build(http_client.clone())?;
 
     let nodes = mega.fetch_public_nodes(url.as_str()).await?;
     let node = nodes.roots().filter(|node| node.kind().is_file()).collect::<Vec<_>>()[0];
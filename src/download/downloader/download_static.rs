@@ -1,11 +1,13 @@
-use std::{error::Error, path::PathBuf};
+use std::{error::Error, path::PathBuf, time::Duration};
 use futures::StreamExt;
 use reqwest::IntoUrl;
 use tokio::{fs::{self, File}, io::AsyncWriteExt};
+use crate::download::redact::redact_error;
 
 
 
-pub async fn download_static<U, F>(url: U, output: &PathBuf, on_progress: F) -> Result<(), Box<dyn Error>>
+/// Stream `url` to `output`, failing if no data arrives for `idle_timeout`.
+pub async fn download_static<U, F>(http_client: &reqwest::Client, url: U, output: &PathBuf, idle_timeout: Duration, on_progress: F) -> Result<(), Box<dyn Error>>
 where
     U: IntoUrl,
     F: Fn(u64, u64)
@@ -13,15 +15,22 @@ where
     fs::create_dir_all(output.parent().unwrap()).await?;
     let mut file = File::create(output).await?;
 
-    let request = reqwest::get(url).await?;
+    let request = http_client.get(url).send().await
+        .and_then(|response| response.error_for_status())
+        .map_err(redact_error)?;
     let total_size = request.content_length().unwrap_or(0);
     let mut stream = request.bytes_stream();
     let mut current_size: u64 = 0;
 
     on_progress(total_size, current_size);
 
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result?;
+    loop {
+        let chunk_result = match tokio::time::timeout(idle_timeout, stream.next()).await {
+            Ok(Some(chunk_result)) => chunk_result,
+            Ok(None) => break,
+            Err(_) => return Err(Box::new(std::io::Error::new(std::io::ErrorKind::TimedOut, format!("no data received for {}s", idle_timeout.as_secs())))),
+        };
+        let chunk = chunk_result.map_err(redact_error)?;
         file.write_all(&chunk).await?;
         current_size += chunk.len() as u64;
 
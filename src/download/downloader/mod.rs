@@ -1,4 +1,4 @@
-use std::{error::Error, path::PathBuf};
+use std::{error::Error, path::PathBuf, time::Duration};
 use reqwest::IntoUrl;
 use self::{download_mega::download_mega, download_static::download_static};
 
@@ -8,7 +8,7 @@ mod download_mega;
 
 
 #[allow(dead_code, unreachable_code)]
-pub async fn download<U, F>(url: U, output: &PathBuf, on_progress: F) -> Result<(), Box<dyn Error>>
+pub async fn download<U, F>(http_client: &reqwest::Client, url: U, output: &PathBuf, idle_timeout: Duration, on_progress: F) -> Result<(), Box<dyn Error>>
 where
     U: IntoUrl,
     F: Fn(u64, u64)
@@ -18,14 +18,14 @@ where
 
     // TODO: Better error handling (I'm too lazy. . .)
 
-    match url.domain() {
+    match url.host_str() {
         Some("mega.nz") | Some("mega.co.nz") => {
             panic!("Mega download W.I.P.");
-            download_mega(url, output, on_progress).await?;
+            download_mega(http_client, url, output, on_progress).await?;
         },
         // Assume everything else is just static.
         Some(_) => {
-            download_static(url, output, on_progress).await?;
+            download_static(http_client, url, output, idle_timeout, on_progress).await?;
         },
         _ => { },
     }
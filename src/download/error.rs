@@ -1,23 +1,87 @@
 
 use core::fmt;
-use std::error::Error;
+use std::{error::Error, path::PathBuf};
 
 
 
 #[derive(Debug, Clone)]
 pub enum DownloadError {
+    ConfigNotFound(Vec<PathBuf>),
+    ConfigInvalidKey(String),
+    ConfigInvalid { path: PathBuf, line: usize, key: Option<String>, message: String },
+    ConfigTooNew(PathBuf, u32),
+    ProfileNotFound(String),
+    ApiKeyMissing(PathBuf),
+    InvalidApiKey,
+    ApiKeyConflict(PathBuf),
+    ApiKeyCommandFailed(String, String),
     LibraryFailLoad,
     LibraryGameIdMismatch,
+    LibraryMove(String),
     GameNoExecutable,
+    GameExecutableMissing(PathBuf),
+    WindowsRunnerMissing,
+    GameNotInstalled(i64),
+    GameNotFound(String),
+    GameAmbiguous(String, Vec<(i64, String)>),
+    CollectionInvalid(String),
+    JamInvalid(String),
+    JamNotFound(String),
+    PromptUnavailable(String),
+    SelectUnavailable(String),
+    NotOwned(String),
+    NotFound(String),
+    RateLimited(Option<u64>),
+    Api(String, String),
+    NoUpload(String),
     ExtractFailed(String)
 }
 
 impl fmt::Display for DownloadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            DownloadError::ConfigNotFound(paths) => {
+                write!(f, "Config file not found, looked in:")?;
+                for path in paths {
+                    write!(f, "\n  {}", path.display())?;
+                }
+                Ok(())
+            },
+            DownloadError::ConfigInvalidKey(key) => write!(f, "Unknown config key \"{}\".", key),
+            DownloadError::ConfigInvalid { path, line, key: Some(key), message } => write!(f, "{}:{}: invalid value for \"{}\": {}.", path.display(), line, key, message),
+            DownloadError::ConfigInvalid { path, line, key: None, message } => write!(f, "{}:{}: {}.", path.display(), line, message),
+            DownloadError::ConfigTooNew(path, version) => write!(f, "{} is config version {}, which is newer than this build supports, update itch-io-downloader.", path.display(), version),
+            DownloadError::ProfileNotFound(profile) => write!(f, "No profile named \"{}\" in the config.", profile),
+            DownloadError::ApiKeyMissing(path) => write!(f, "No itch.io API key set in {}, run `config init` or `config set api_key <key>`.", path.display()),
+            DownloadError::InvalidApiKey => write!(f, "itch.io rejected the API key, check it or create a new one at https://itch.io/user/settings/api-keys & run `config init`."),
+            DownloadError::ApiKeyConflict(path) => write!(f, "Both api_key & api_key_command are set in {}, remove one of them.", path.display()),
+            DownloadError::ApiKeyCommandFailed(command, msg) => write!(f, "api_key_command \"{}\" failed: {}.", command, msg),
             DownloadError::LibraryFailLoad => write!(f, "Library failed loading."),
             DownloadError::LibraryGameIdMismatch => write!(f, "Game ID Mismatch."),
+            DownloadError::LibraryMove(msg) => write!(f, "Library move failed: {}.", msg),
             DownloadError::GameNoExecutable => write!(f, "Game failed to find executable."),
+            DownloadError::GameExecutableMissing(path) => write!(f, "Game executable {} doesn't exist, change it with `game set --exe`.", path.display()),
+            DownloadError::WindowsRunnerMissing => write!(f, "Game is a windows build, set `windows_runner` in the config (e.g. `wine`) to run it."),
+            DownloadError::GameNotInstalled(game_id) => write!(f, "Game {} is not installed.", game_id),
+            DownloadError::GameNotFound(query) => write!(f, "No installed game matches \"{}\", use a game id, itch.io URL or author/game.", query),
+            DownloadError::GameAmbiguous(query, candidates) => {
+                write!(f, "\"{}\" matches multiple games:", query)?;
+                for (game_id, title) in candidates {
+                    write!(f, "\n  {:>10}  {}", game_id, title)?;
+                }
+                Ok(())
+            },
+            DownloadError::CollectionInvalid(collection) => write!(f, "\"{}\" is not a collection, use a collection id or URL like https://itch.io/c/123456/name.", collection),
+            DownloadError::JamInvalid(jam) => write!(f, "Couldn't find the entries of jam \"{}\", use a jam URL like https://itch.io/jam/name.", jam),
+            DownloadError::JamNotFound(jam) => write!(f, "No installed game is an entry of jam \"{}\", see `jam list`.", jam),
+            DownloadError::PromptUnavailable(prompt) => write!(f, "Cannot answer prompt \"{}\" without input, pass --yes or --no to answer confirmations automatically.", prompt),
+            DownloadError::SelectUnavailable(prompt) => write!(f, "Cannot answer \"{}\" without an interactive terminal, --yes & --no only answer confirmations.", prompt),
+            DownloadError::NotOwned(resource) => write!(f, "Not allowed to download {}, buy or claim it on itch.io, or use the --profile of the account that owns it.", resource),
+            DownloadError::NotFound(resource) => write!(f, "Couldn't find {} on itch.io, check the id or URL, it may have been deleted or made private.", resource),
+            DownloadError::RateLimited(Some(seconds)) => write!(f, "itch.io is rate limiting requests, wait {} seconds & try again.", seconds),
+            DownloadError::RateLimited(None) => write!(f, "itch.io is rate limiting requests, wait a minute & try again."),
+            DownloadError::Api(resource, msg) => write!(f, "itch.io API error for {}: {}.", resource, msg),
+            DownloadError::NoUpload(reason) => write!(f, "No upload can be installed: {}.", reason),
             DownloadError::ExtractFailed(msg) => write!(f, "Extraction failed {}", msg),
         }
     }
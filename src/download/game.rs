@@ -1,29 +1,84 @@
 #![allow(dead_code)]
 
-use std::{error::Error, fs::DirEntry, path::PathBuf};
+use std::{collections::BTreeMap, error::Error, fs::DirEntry, path::{Path, PathBuf}};
 use serde::{Deserialize, Serialize};
 use tokio::{fs, process::Command};
-use super::{api::{itch_api_game_uploads, GameUpload}, config::Config, error::DownloadError};
-
+use super::{api::ItchClient, config::Config, error::DownloadError, policy::{Platform, UploadPolicy, UploadTrack}, utils::dir_size};
+
+
+
+/// Per game overrides for how the game is started.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LaunchOptions {
+    /// Executable relative to the game directory, instead of guessing one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Working directory relative to the game directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
 
+impl LaunchOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Game {
     pub game_id: i64,
     pub upload_id: i64,
+    /// Build of the upload, uploads pushed with butler keep their id & get a new build.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<i64>,
     pub title: String,
     pub description: String,
     pub url: String,
     pub directory: String,
+    /// Profile whose account installed the game, `None` for the top level API key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "LaunchOptions::is_empty")]
+    pub launch: LaunchOptions,
+    /// Purchase the game was downloaded with, needed for paid games.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_key_id: Option<i64>,
+    /// Name of the jam the game was installed from as an entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jam: Option<String>,
+    /// Platform of the installed upload, `None` for games installed before platforms were tracked, which are windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    /// Upload or channel picked when several uploads qualified, updates stay on it.
+    /// Without one, updates stay on the installed upload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<UploadTrack>,
 }
 
 
 
 impl Game {
 
-    pub async fn is_downloaded(&mut self, config: &Config) -> Result<bool, Box<dyn Error>> {
+    pub fn path(&self, config: &Config) -> PathBuf {
         let mut game_path = PathBuf::from(&config.games_dir);
         game_path.push(&self.directory);
+        game_path
+    }
+
+    /// Size of the game directory on disk, 0 if not downloaded.
+    pub async fn size(&mut self, config: &Config) -> Result<u64, Box<dyn Error>> {
+        if !self.is_downloaded(config).await? {
+            return Ok(0);
+        }
+        dir_size(&self.path(config)).await
+    }
+
+    pub async fn is_downloaded(&mut self, config: &Config) -> Result<bool, Box<dyn Error>> {
+        let game_path = self.path(config);
 
         if !fs::try_exists(&game_path).await? {
             return Ok(false);
@@ -34,33 +89,74 @@ impl Game {
         Ok(meta.is_dir())
     }
 
+    /// Track updates follow, games without a recorded track stay on the installed upload.
+    pub fn followed_track(&self) -> UploadTrack {
+        self.track.clone().unwrap_or(UploadTrack::Upload(self.upload_id))
+    }
+
     pub async fn is_latest(&mut self, config: &Config) -> Result<bool, Box<dyn Error>> {
         if !self.is_downloaded(config).await? {
             return Ok(false);
         }
 
-        let mut game_uploads = itch_api_game_uploads(&config.api_key, &self.game_id).await?.uploads;
-        game_uploads.sort_by(|a, b| {
-            a.id.cmp(&b.id)
-        });
-        let game_upload = game_uploads.iter()
-            .filter(|game_upload| game_upload.p_windows)
-            .collect::<Vec<&GameUpload>>().first().unwrap().to_owned();
+        let game_uploads = ItchClient::new(config, self.profile.as_deref()).await?.game_uploads(&self.game_id, self.download_key_id).await?.uploads;
+        let track = self.followed_track();
+        let game_upload = match UploadPolicy::new(config, Some(self.platform.unwrap_or(Platform::Windows))).follow(Some(&track)).pick(&game_uploads) {
+            Ok((candidate, _)) => candidate.upload,
+            Err(reason) => return Err(Box::new(DownloadError::NoUpload(reason))),
+        };
 
-        Ok(game_upload.id <= self.upload_id)
+        // Up to date while the policy still picks the installed upload & its build is unchanged.
+        // Games installed before builds were recorded count as outdated once, so their build gets recorded.
+        Ok(game_upload.id == self.upload_id && (game_upload.build_id.is_none() || game_upload.build_id == self.build_id))
     }
 
-    pub async fn start(&mut self, config: &Config) -> Result<(), Box<dyn Error>> {
-
-        let mut search_path = PathBuf::from(&config.games_dir);
-        search_path.push(&self.directory);
-        let executable_path = find_executable(search_path)?;
-
-        if executable_path.is_some() {
-            let full_path = fs::canonicalize(executable_path.unwrap()).await?;
-            // I'm dumb and don't know how canonicalize works, it adds \\?\ to beginning?
-            let (_, exec_path) = full_path.to_str().unwrap().split_at(4);
-            let _program = Command::new(exec_path).spawn().unwrap();
+    /// Start the game, `extra_args` are added after the configured launch arguments.
+    pub async fn start(&mut self, config: &Config, extra_args: &[String]) -> Result<(), Box<dyn Error>> {
+        let game_path = self.path(config);
+        let platform = self.platform.unwrap_or(Platform::Windows);
+
+        let executable_path = match &self.launch.exe {
+            Some(exe) => {
+                let exe_path = game_path.join(exe);
+                if !fs::try_exists(&exe_path).await? {
+                    return Err(Box::new(DownloadError::GameExecutableMissing(exe_path)));
+                }
+                Some(exe_path)
+            },
+            None => find_executable(game_path.clone(), platform)?,
+        };
+
+        if let Some(executable_path) = executable_path {
+            let full_path = fs::canonicalize(executable_path).await?;
+            // Canonical paths on windows start with \\?\, which some games can't handle.
+            let exec_path = full_path.to_str().unwrap();
+            let exec_path = exec_path.strip_prefix(r"\\?\").unwrap_or(exec_path);
+
+            let mut command = match platform {
+                // Windows builds on other platforms run through the configured runner, like wine.
+                Platform::Windows if Platform::host() != Platform::Windows => {
+                    let runner = config.windows_runner.as_deref().unwrap_or_default();
+                    let mut runner = runner.split_whitespace();
+                    let mut command = Command::new(runner.next().ok_or(DownloadError::WindowsRunnerMissing)?);
+                    command.args(runner).arg(exec_path);
+                    command
+                },
+                // App bundles are started with open, game arguments go after --args.
+                Platform::Osx if full_path.is_dir() => {
+                    let mut command = Command::new("open");
+                    command.arg("-a").arg(exec_path).arg("--args");
+                    command
+                },
+                _ => Command::new(exec_path),
+            };
+            command.args(&self.launch.args)
+                .args(extra_args)
+                .envs(&self.launch.env);
+            if let Some(cwd) = &self.launch.cwd {
+                command.current_dir(game_path.join(cwd));
+            }
+            let _program = command.spawn()?;
         } else {
             return Err(Box::new(DownloadError::GameNoExecutable));
         }
@@ -74,46 +170,65 @@ impl Game {
 
 
 
-static SEARCH_BLACKLIST: &'static [&'static str] = &[
+static SEARCH_BLACKLIST: &[&str] = &[
     "UnityCrashHandler64.exe",
     "UnityCrashHandler32.exe" // Don't actually know if this exists but include it just incase.
 ];
 
-fn is_executable_blacklisted(path: &PathBuf) -> bool {
+fn is_executable_blacklisted(path: &Path) -> bool {
     SEARCH_BLACKLIST.iter().any(|blacklisted| {
         path.to_str().unwrap().ends_with(blacklisted)
     })
 }
 
-fn find_executable_parent(path: PathBuf) -> Result<Option<PathBuf>, Box<dyn Error>> {
+/// If `path` is something that can be started on `platform`.
+fn is_executable(path: &Path, platform: Platform) -> bool {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    match platform {
+        Platform::Windows => path.is_file() && extension == "exe",
+        Platform::Osx => path.is_dir() && extension == "app",
+        Platform::Linux => path.is_file() && LINUX_EXTENSIONS.contains(&extension) && has_exec_bit(path),
+    }
+}
+
+/// Extensions of linux executables, `""` is none.
+static LINUX_EXTENSIONS: &[&str] = &["", "x86_64", "x86", "sh", "AppImage", "appimage", "bin"];
+
+#[cfg(unix)]
+fn has_exec_bit(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn has_exec_bit(_path: &Path) -> bool {
+    true
+}
+
+fn find_executable_parent(path: PathBuf, platform: Platform) -> Result<Option<PathBuf>, Box<dyn Error>> {
     let mut queue: Vec<PathBuf> = vec![ path ];
 
-    while queue.len() > 0 {
+    while !queue.is_empty() {
         let path = queue.remove(0);
 
         if is_executable_blacklisted(&path) {
             continue;
         }
 
-        if path.is_dir() {
+        if is_executable(&path, platform) {
+            return Ok(path.parent().map(PathBuf::from));
+        } else if path.is_dir() {
             for entry in path.read_dir()? {
                 queue.push(entry?.path());
             }
-        } else if path.is_file() {
-            if path.extension().is_some() && path.extension().unwrap() == "exe" {
-                return Ok(match path.parent() {
-                    Some(parent) => Some(PathBuf::from(parent)),
-                    None => None,
-                });
-            }
         }
     }
 
     Ok(None)
 }
 
-fn find_executable(path: PathBuf) -> Result<Option<PathBuf>, Box<dyn Error>> {
-    match find_executable_parent(path)? {
+fn find_executable(path: PathBuf, platform: Platform) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    match find_executable_parent(path, platform)? {
         Some(executable_dir) => {
 
             let mut executables: Vec<DirEntry> = executable_dir.read_dir()?
@@ -121,9 +236,8 @@ fn find_executable(path: PathBuf) -> Result<Option<PathBuf>, Box<dyn Error>> {
                     match item {
                         Ok(item) => {
                             let path = item.path();
-                            return path.is_file() && 
-                                !is_executable_blacklisted(&path) &&
-                                (path.extension().is_some() && path.extension().unwrap() == "exe");
+                            !is_executable_blacklisted(&path) &&
+                                is_executable(&path, platform)
                         },
                         Err(_) => false,
                     }
@@ -0,0 +1,150 @@
+
+use std::{error::Error, path::PathBuf};
+use console::style;
+use indicatif::HumanBytes;
+use serde::Serialize;
+use super::{api::{GameInfoGame, GameUpload, ItchClient}, config::Config, library::Library, policy::{Platform, UploadPolicy}};
+
+
+
+#[derive(Serialize)]
+struct InfoInstall {
+    upload_id: i64,
+    profile: Option<String>,
+    directory: PathBuf,
+    downloaded: bool,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Info {
+    game: GameInfoGame,
+    install: Option<InfoInstall>,
+    uploads: Vec<GameUpload>,
+    selected_upload_id: Option<i64>,
+    selection_reason: String,
+}
+
+
+
+fn format_price(cents: i64) -> String {
+    if cents <= 0 {
+        "Free".into()
+    } else {
+        format!("${}.{:02}", cents / 100, cents % 100)
+    }
+}
+
+fn print_field(name: &str, value: impl std::fmt::Display) {
+    println!("{} {}", style(format!("{:>14}", name)).magenta(), value);
+}
+
+pub async fn show_info(config: &Config, game_id: i64, json: bool) -> Result<(), Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    // Use the account that installed the game, if it is installed.
+    let profile = match library.get_game(config, &game_id) {
+        Some(installed) => installed.profile.clone(),
+        None => config.profile.clone(),
+    };
+    let client = ItchClient::new(config, profile.as_deref()).await?;
+
+    let game = client.game_info(&game_id).await?.game;
+    let download_key_id = client.download_key_id(&game_id).await?;
+    let uploads = client.game_uploads(&game_id, download_key_id).await?.uploads;
+
+    let install = match library.get_game(config, &game_id) {
+        Some(installed) => {
+            let mut installed = installed.clone();
+            Some(InfoInstall {
+                upload_id: installed.upload_id,
+                profile: installed.profile.clone(),
+                directory: installed.path(config),
+                downloaded: installed.is_downloaded(config).await?,
+                size: installed.size(config).await?,
+            })
+        },
+        None => None,
+    };
+
+    let installed_platform = library.get_game(config, &game_id).map(|installed| installed.platform.unwrap_or(Platform::Windows));
+    let track = library.get_game(config, &game_id).map(|installed| installed.followed_track());
+    let (selected_upload_id, selection_reason) = match UploadPolicy::new(config, installed_platform).follow(track.as_ref()).pick(&uploads) {
+        Ok((candidate, reason)) => (Some(candidate.upload.id), reason),
+        Err(reason) => (None, reason),
+    };
+
+    let info = Info { game, install, uploads, selected_upload_id, selection_reason };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let game = &info.game;
+    println!("{} {}", style(&game.title).magenta().bold(), style(format!("({})", game.id)).cyan());
+    if let Some(short_text) = &game.short_text {
+        println!("{}", style(short_text).dim());
+    }
+    println!();
+    print_field("URL", &game.url);
+    print_field("Author", format!("{} ({})", game.user.username, game.user.url));
+    print_field("Type", format!("{} / {}", game.r#type, game.classification));
+    print_field("Price", format_price(game.min_price));
+    print_field("Can be bought", game.can_be_bought);
+    print_field("Has demo", game.has_demo);
+    print_field("Platforms", game.platforms().join(", "));
+    print_field("Published", &game.published_at);
+
+    println!();
+    match &info.install {
+        Some(install) => {
+            print_field("Installed", format!("upload {}", install.upload_id));
+            if let Some(profile) = &install.profile {
+                print_field("Profile", profile);
+            }
+            print_field("Directory", install.directory.display());
+            if install.downloaded {
+                print_field("Size", HumanBytes(install.size));
+            } else {
+                print_field("Size", style("files missing").red());
+            }
+        },
+        None => print_field("Installed", "no"),
+    }
+
+    println!();
+    println!("{}", style(format!("  {:>10}  {:<32}  {:>10}  {:<22}  {:<16}  {}", "UPLOAD", "NAME", "SIZE", "PLATFORMS", "CHANNEL", "VERSION")).magenta().bold());
+    for game_upload in &info.uploads {
+        let marker = if Some(game_upload.id) == info.selected_upload_id { "*" } else { " " };
+        let size = game_upload.size.map(|size| HumanBytes(size as u64).to_string()).unwrap_or("-".into());
+        let version = match &game_upload.build {
+            Some(build) => build.user_version.clone().unwrap_or(build.version.to_string()),
+            None => "-".into(),
+        };
+        let mut flags = Vec::new();
+        if game_upload.demo { flags.push("demo"); }
+        if game_upload.preorder { flags.push("preorder"); }
+
+        println!("{} {:>10}  {}  {:>10}  {:<22}  {:<16}  {} {}",
+            style(marker).green().bold(),
+            style(game_upload.id).cyan(),
+            style(format!("{:<32}", game_upload.name())).magenta().bright(),
+            size,
+            game_upload.platforms().join(", "),
+            game_upload.channel_name.as_deref().unwrap_or("-"),
+            version,
+            style(flags.join(" ")).yellow(),
+        );
+    }
+
+    println!();
+    match info.selected_upload_id {
+        Some(upload_id) => println!("{} {}: {}", style("Would install upload").magenta(), style(upload_id).cyan(), info.selection_reason),
+        None => println!("{} {}", style("No upload can be installed:").red(), info.selection_reason),
+    }
+
+    Ok(())
+}
+
+
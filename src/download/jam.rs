@@ -0,0 +1,133 @@
+
+use std::{collections::BTreeMap, error::Error};
+use console::style;
+use indicatif::HumanBytes;
+use super::{api::ItchClient, config::Config, error::DownloadError, install, library::Library, policy::UploadPolicy, prompt};
+
+
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// `entries.json` URL & name of a jam, read from its page.
+/// The page only links the listing by numeric jam id, so it has to be fetched.
+async fn jam_page(client: &ItchClient, jam: &str) -> Result<(String, String), Box<dyn Error>> {
+    let page_url = match reqwest::Url::parse(jam.trim()) {
+        Ok(url) if url.path().starts_with("/jam/") => url,
+        _ => return Err(Box::new(DownloadError::JamInvalid(jam.into()))),
+    };
+    // The link is in embedded JSON, which may escape slashes.
+    let html = client.get_text(page_url.as_str(), &format!("jam page {}", page_url)).await?.replace("\\/", "/");
+
+    let entries_path = html.find("/entries.json")
+        .and_then(|end| {
+            let start = html[..end].rfind("/jam/")?;
+            html[start + "/jam/".len()..end].parse::<i64>().ok()?;
+            Some(&html[start..end + "/entries.json".len()])
+        });
+    let entries_url = match entries_path {
+        Some(entries_path) => page_url.join(entries_path)?.to_string(),
+        None => return Err(Box::new(DownloadError::JamInvalid(jam.into()))),
+    };
+
+    let name = html.split_once("<title>")
+        .and_then(|(_, rest)| rest.split_once("</title>"))
+        .map(|(title, _)| decode_entities(title.trim().trim_end_matches("- itch.io").trim()))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| page_url.path_segments().and_then(|mut segments| segments.nth(1)).unwrap_or(jam).to_string());
+
+    Ok((entries_url, name))
+}
+
+
+
+/// Install every entry of a jam with a build for the platforms uploads are picked for & tag the newly installed ones with the jam name,
+/// returns if every entry installed successfully.
+pub async fn jam_install(config: &Config, jam: &str, force: bool) -> Result<bool, Box<dyn Error>> {
+    // Jam pages are public & may be on any host given, so the API key is never sent.
+    let client = ItchClient::anonymous(config)?;
+    let (entries_url, name) = jam_page(&client, jam).await?;
+    let entries = client.jam_entries(&entries_url).await?.jam_games;
+
+    // Same platforms `install` picks uploads for, so entries it would refuse are skipped up front.
+    let policy = UploadPolicy::new(config, None);
+    let (installable, skipped): (Vec<_>, Vec<_>) = entries.iter().partition(|entry| {
+        entry.game.platforms.is_empty() || entry.game.platforms.iter().any(|platform| policy.targets(platform))
+    });
+    println!("{} {} {}", style("Jam").magenta(), style(&name).magenta().bold(), style(format!("({} entries)", entries.len())).cyan());
+    for entry in &skipped {
+        println!("  {} {}", style(format!("skipped, no {} build", policy.platform_names())).dim(), style(&entry.game.title).dim());
+    }
+
+    let game_ids = installable.iter().map(|entry| entry.game.id).collect::<Vec<i64>>();
+    let already_installed = Library::load(config).await?.games.iter().map(|game| game.game_id).collect::<Vec<i64>>();
+    let installed = install(config, &game_ids, force, false).await?;
+
+    // Tag only entries this run added, games installed before belong to the user or another jam,
+    // & `jam uninstall` must not delete them.
+    let mut library = Library::load(config).await?;
+    for game in library.games.iter_mut().filter(|game| game_ids.contains(&game.game_id) && !already_installed.contains(&game.game_id) && game.jam.is_none()) {
+        game.jam = Some(name.clone());
+    }
+    library.save(config).await?;
+
+    Ok(installed)
+}
+
+/// List every jam with installed entries.
+pub async fn jam_list(config: &Config) -> Result<(), Box<dyn Error>> {
+    let library = Library::load(config).await?;
+
+    let mut jams: BTreeMap<String, u64> = BTreeMap::new();
+    for game in &library.games {
+        if let Some(jam) = &game.jam {
+            *jams.entry(jam.clone()).or_default() += 1;
+        }
+    }
+
+    if jams.is_empty() {
+        println!("{}", style("No jam entries installed").black().on_red());
+        return Ok(());
+    }
+
+    println!("{}", style(format!("{:>7}  {}", "ENTRIES", "JAM")).magenta().bold());
+    for (jam, count) in &jams {
+        println!("{:>7}  {}", style(count).cyan(), style(jam).magenta().bright());
+    }
+
+    Ok(())
+}
+
+/// Uninstall every installed entry of a jam.
+pub async fn jam_uninstall(config: &Config, jam: &str) -> Result<(), Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    let entries = library.games.iter()
+        .filter(|game| game.jam.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(jam.trim())))
+        .map(|game| (game.game_id, game.title.clone()))
+        .collect::<Vec<(i64, String)>>();
+    if entries.is_empty() {
+        return Err(Box::new(DownloadError::JamNotFound(jam.into())));
+    }
+
+    for (_, title) in &entries {
+        println!("  {}", style(title).magenta().bright());
+    }
+    if !prompt::confirm(config.prompt, &format!("Do you want to uninstall these {} games?", entries.len()))? {
+        return Ok(());
+    }
+
+    let mut freed = 0;
+    for (game_id, _) in &entries {
+        freed += library.uninstall_game(config, game_id).await?;
+    }
+    println!("{} {} {}", style("Uninstalled").magenta(), style(format!("{} games", entries.len())).magenta().bold(), style(format!("freed {}", HumanBytes(freed))).cyan());
+
+    Ok(())
+}
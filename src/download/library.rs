@@ -1,17 +1,107 @@
 
-use std::{error::Error, fmt::Write, path::PathBuf};
+use std::{error::Error, fmt::Write, path::PathBuf, time::Duration};
 use console::style;
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressState, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use tokio::{fs, io::AsyncWriteExt};
-use crate::download::{api::{itch_api_game_info, itch_api_game_uploads, itch_api_upload_download, GameUpload}, downloader::download, utils::extract_archive};
-use super::{config::Config, error::DownloadError, game::Game};
+use crate::download::{api::{GameUpload, ItchClient}, downloader::download, manifest::Manifest, retry::{classify, retry}, utils::{extract_archive, merge_dir}};
+use super::{config::Config, error::DownloadError, game::{Game, LaunchOptions}, policy::{Candidate, Platform, UploadPolicy, UploadTrack}, prompt, relocate::MOVE_JOURNAL};
 
 
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Library {
     pub games: Vec<Game>,
+    /// Collections whose new games `update --all` installs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<LibraryCollection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LibraryCollection {
+    pub id: i64,
+    pub title: String,
+}
+
+
+
+/// The upload to install & the track to remember. When `pick` is set, several uploads qualify & none is on the followed track,
+/// the user picks one & follows it from then on. Otherwise, or without a terminal, the policy's best upload is used.
+fn choose_upload<'a>(config: &Config, policy: &UploadPolicy, uploads: &'a [GameUpload], pick: bool) -> Result<(Candidate<'a>, Option<UploadTrack>), Box<dyn Error>> {
+    let candidates = match policy.rank(uploads) {
+        Ok((candidates, _)) => candidates,
+        Err(reason) => return Err(Box::new(DownloadError::NoUpload(reason))),
+    };
+
+    if let Some(candidate) = policy.on_track(&candidates) {
+        return Ok((candidate, None));
+    }
+    if !pick || candidates.len() == 1 || !prompt::can_select(config.prompt) {
+        return Ok((candidates[0], None));
+    }
+
+    let items = candidates.iter()
+        .map(|candidate| {
+            let game_upload = candidate.upload;
+            let size = game_upload.size.map(|size| HumanBytes(size as u64).to_string()).unwrap_or("-".into());
+            let mut flags = Vec::new();
+            if game_upload.demo { flags.push("demo"); }
+            if game_upload.preorder { flags.push("preorder"); }
+            if game_upload.r#type != "default" { flags.push(game_upload.r#type.as_str()); }
+            format!("{:<32}  {:>10}  {:<22}  {:<16}  {}",
+                game_upload.name(),
+                size,
+                game_upload.platforms().join(", "),
+                game_upload.channel_name.as_deref().unwrap_or("-"),
+                flags.join(" "),
+            ).trim_end().to_string()
+        })
+        .collect::<Vec<String>>();
+    let selection = prompt::select(config.prompt, "Several uploads qualify, which one do you want to install?", &items, 0)?;
+
+    let candidate = candidates[selection];
+    Ok((candidate, Some(UploadTrack::of(candidate.upload))))
+}
+
+
+
+/// Download an upload & extract it into `out_dir`.
+pub async fn download_upload(config: &Config, client: &ItchClient, game_upload: &GameUpload, download_key_id: Option<i64>, out_dir: &PathBuf) -> Result<(), Box<dyn Error>> {
+    // Upload link to download.
+    let game_download = client.upload_download(&game_upload.id, download_key_id).await?;
+
+    // Download game.
+    println!("{}", style("Initializing download").magenta());
+    let mut temp_path = PathBuf::from(&config.games_dir);
+    temp_path.push("temp");
+    temp_path.push(&game_upload.filename);
+
+    let progress_bar = ProgressBar::new(0);
+    progress_bar.set_style(ProgressStyle::with_template("{msg:.magenta} {spinner:.cyan} [{elapsed_precise:.cyan}] [{bar:20.magenta/cyan}] {bytes:.cyan}/{total_bytes:.cyan} ({eta:.cyan})")
+        .unwrap()
+        .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+        .progress_chars("#>-"));
+    progress_bar.set_message("Downloading");
+
+    let network = client.network();
+    retry(network, "Download", || async {
+        download(client.http(), game_download.url.as_str(), &temp_path, Duration::from_secs(network.download_timeout), |total_size, current_size| {
+            progress_bar.set_position(current_size);
+            progress_bar.set_length(total_size);
+        }).await.map_err(classify)
+    }).await?;
+
+    // Extract game archive.
+    println!("{}", style("Extracting game").magenta());
+    if fs::try_exists(out_dir).await? {
+        fs::remove_dir_all(out_dir).await?;
+    }
+    extract_archive(&temp_path, out_dir).await?;
+
+    // Cleanup temp
+    fs::remove_file(&temp_path).await?;
+
+    Ok(())
 }
 
 
@@ -30,6 +120,10 @@ impl Library {
 
     pub async fn load(config: &Config) -> Result<Library, Box<dyn Error>> {
         let path = Self::get_library_json_file(config).await?;
+
+        if fs::try_exists(config.games_dir.join(MOVE_JOURNAL)).await? {
+            eprintln!("{}", style("A library move was interrupted, run `library move` again to finish it").black().on_yellow());
+        }
         
         match fs::metadata(&path).await {
             Ok(meta) => {
@@ -44,7 +138,7 @@ impl Library {
                 if err.kind() != tokio::io::ErrorKind::NotFound {
                     Err(Box::new(err))
                 } else {
-                    Ok(Self { games: Vec::new() })
+                    Ok(Self { games: Vec::new(), collections: Vec::new() })
                 }
             }
         }
@@ -54,7 +148,7 @@ impl Library {
         let str = serde_json::to_string_pretty(self)?;
         let path = Self::get_library_json_file(config).await?;
         let mut file = fs::File::create(path).await?;
-        file.write(str.as_bytes()).await?;
+        file.write_all(str.as_bytes()).await?;
         file.flush().await?;
         Ok(())
     }
@@ -71,82 +165,99 @@ impl Library {
     }
 
     pub fn remove_game(&mut self, _config: &Config, game: &Game) {
-        match self.games.iter().enumerate().find(|(_, g)| { g.game_id.eq(&game.game_id) }) {
-            Some((i, _)) => { self.games.remove(i); },
-            _ => { },
+        if let Some((i, _)) = self.games.iter().enumerate().find(|(_, g)| { g.game_id.eq(&game.game_id) }) {
+            self.games.remove(i);
+        }
+    }
+
+
+
+    /// Delete the game's files & remove it from the library, returns bytes freed.
+    pub async fn uninstall_game(&mut self, config: &Config, game_id: &i64) -> Result<u64, Box<dyn Error>> {
+        let mut game = match self.get_game(config, game_id) {
+            Some(game) => game.clone(),
+            None => return Err(Box::new(DownloadError::GameNotInstalled(*game_id))),
+        };
+
+        let size = game.size(config).await?;
+        if game.is_downloaded(config).await? {
+            fs::remove_dir_all(game.path(config)).await?;
         }
+
+        self.remove_game(config, &game);
+        self.save(config).await?;
+        Manifest::remove(config, game_id).await?;
+
+        Ok(size)
     }
 
 
 
-    pub async fn download_game(&mut self, config: &Config, game_id: i64) -> Result<Option<&Game>, Box<dyn Error>> {
+    /// Install or reinstall a game, `pick` asks which upload to install when several qualify.
+    pub async fn download_game(&mut self, config: &Config, game_id: i64, pick: bool) -> Result<Option<&Game>, Box<dyn Error>> {
         println!("{}", style("Getting game info").magenta());
 
+        // Reinstalls use the account that installed the game & keep its launch options, jam & upload track.
+        // Only a picked track is recorded, the implicit one moves with the installed upload.
+        let (profile, launch, jam, installed_platform, track, followed_track) = match self.get_game(config, &game_id) {
+            Some(game) => (game.profile.clone(), game.launch.clone(), game.jam.clone(), Some(game.platform.unwrap_or(Platform::Windows)), game.track.clone(), Some(game.followed_track())),
+            None => (config.profile.clone(), LaunchOptions::default(), None, None, None, None),
+        };
+        let client = ItchClient::new(config, profile.as_deref()).await?;
+
         // Get game info.
-        let game_info = itch_api_game_info(&config.api_key, &game_id).await?.game;
+        let game_info = client.game_info(&game_id).await?.game;
         if game_info.id != game_id {
             return Err(Box::new(DownloadError::LibraryGameIdMismatch));
         }
 
-        // Get latest upload.
-        let mut game_uploads = itch_api_game_uploads(&config.api_key, &game_info.id).await?.uploads;
-        game_uploads.sort_by(|a, b| {
-            a.id.cmp(&b.id)
-        });
-        let game_upload = game_uploads.iter()
-            .filter(|game_upload| {
-                if !game_upload.p_windows { return false; }
-                match &game_upload.host {
-                    Some(host) => {
-                        host == "mega.nz" ||
-                        host == "mega.co.nz"
-                    },
-                    None => true
-                }
-            })
-            .collect::<Vec<&GameUpload>>().first().unwrap().to_owned();
-        println!("{} {}", style("File to download").magenta(), style(&game_upload.filename).magenta().bold());
-
-        // Upload link to download.
-        let game_download = itch_api_upload_download(&config.api_key, &game_upload.id).await?;
-
-        // Download game.
-        println!("{}", style("Initializing download").magenta());
-        let mut temp_path = PathBuf::from(&config.games_dir);
-        temp_path.push("temp");
-        temp_path.push(&game_upload.filename);
-
-        let progress_bar = ProgressBar::new(0);
-        progress_bar.set_style(ProgressStyle::with_template("{msg:.magenta} {spinner:.cyan} [{elapsed_precise:.cyan}] [{bar:20.magenta/cyan}] {bytes:.cyan}/{total_bytes:.cyan} ({eta:.cyan})")
-            .unwrap()
-            .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-            .progress_chars("#>-"));
-        progress_bar.set_message("Downloading");
-
-        download(game_download.url, &temp_path, |total_size, current_size| {
-            progress_bar.set_position(current_size);
-            progress_bar.set_length(total_size);
-        }).await?;
+        // Paid games need the key of the purchase.
+        let download_key_id = client.download_key_id(&game_info.id).await?;
 
-        // Extract game archive.
-        println!("{}", style("Extracting game").magenta());
+        // Get latest upload.
+        let game_uploads = client.game_uploads(&game_info.id, download_key_id).await?.uploads;
+        let policy = UploadPolicy::new(config, installed_platform).follow(followed_track.as_ref());
+        let (candidate, picked_track) = choose_upload(config, &policy, &game_uploads, pick)?;
+        let (game_upload, platform) = (candidate.upload, candidate.platform);
+        let track = picked_track.or(track);
+        println!("{} {} {}", style("File to download").magenta(), style(&game_upload.filename).magenta().bold(), style(format!("({})", platform.name())).cyan());
+
+        // Download & extract to staging.
         let games_path = PathBuf::from(&config.games_dir);
-        let mut game_path = PathBuf::from(&games_path);
-        game_path.push(game_info.id.to_string());
-        extract_archive(&temp_path, &game_path).await?;
+        let mut staging_path = PathBuf::from(&games_path);
+        staging_path.push("temp");
+        staging_path.push(format!("{}-staging", game_info.id));
+        download_upload(config, &client, game_upload, download_key_id, &staging_path).await?;
 
-        // Cleanup temp
+        // Record manifest of extracted files.
         println!("{}", style("Finishing installation").magenta());
-        fs::remove_file(&temp_path).await?;
+        let manifest = Manifest::create(game_info.id, game_upload.id, &staging_path).await?;
+
+        // Move into game directory, then drop files of the previous version the new one doesn't have.
+        let mut game_path = PathBuf::from(&games_path);
+        game_path.push(game_info.id.to_string());
+        let previous_manifest = Manifest::load(config, &game_info.id).await?;
+        merge_dir(&staging_path, &game_path).await?;
+        if let Some(previous_manifest) = previous_manifest {
+            manifest.remove_dropped(&previous_manifest, &game_path).await?;
+        }
+        manifest.save(config).await?;
 
         // Add game to self
         self.set_game(config, &Game {
             game_id: game_info.id,
             upload_id: game_upload.id,
+            build_id: game_upload.build_id,
             title: game_info.title,
             description: game_info.short_text.unwrap_or("No description".into()),
             url: game_info.url,
             directory: game_path.strip_prefix(&games_path)?.to_str().unwrap().into(),
+            profile,
+            launch,
+            download_key_id,
+            jam,
+            platform: Some(platform),
+            track,
         });
 
         // Update library_info.json
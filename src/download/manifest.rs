@@ -0,0 +1,192 @@
+
+use std::{collections::HashSet, error::Error, path::{Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::{AsyncReadExt, AsyncWriteExt}};
+use super::config::Config;
+
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ManifestFile {
+    /// Path relative to the game directory, `/` separated.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Every file extracted for an install, stored at `games_dir/manifests/<game_id>.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Manifest {
+    pub game_id: i64,
+    pub upload_id: i64,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty()
+    }
+
+    /// Files that need restoring.
+    pub fn broken(&self) -> impl Iterator<Item = &String> {
+        self.missing.iter().chain(self.modified.iter())
+    }
+}
+
+
+
+async fn hash_file(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Every file under `dir`, relative to `dir` & `/` separated.
+pub async fn list_files(dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut queue: Vec<PathBuf> = vec![ dir.to_path_buf() ];
+
+    while let Some(path) = queue.pop() {
+        let mut entries = fs::read_dir(&path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                queue.push(entry.path());
+            } else {
+                let relative = entry.path().strip_prefix(dir)?
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<String>>()
+                    .join("/");
+                files.push(relative);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+
+
+impl Manifest {
+
+    pub async fn create(game_id: i64, upload_id: i64, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut files = Vec::new();
+        for path in list_files(dir).await? {
+            let full_path = dir.join(&path);
+            files.push(ManifestFile {
+                size: fs::metadata(&full_path).await?.len(),
+                sha256: hash_file(&full_path).await?,
+                path,
+            });
+        }
+
+        Ok(Self { game_id, upload_id, files })
+    }
+
+    fn get_manifest_file(config: &Config, game_id: &i64) -> PathBuf {
+        let mut path = PathBuf::from(&config.games_dir);
+        path.push("manifests");
+        path.push(format!("{}.json", game_id));
+        path
+    }
+
+    pub async fn load(config: &Config, game_id: &i64) -> Result<Option<Self>, Box<dyn Error>> {
+        match fs::read_to_string(Self::get_manifest_file(config, game_id)).await {
+            Ok(str) => Ok(Some(serde_json::from_str(&str)?)),
+            Err(err) if err.kind() == tokio::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    pub async fn save(&self, config: &Config) -> Result<(), Box<dyn Error>> {
+        let path = Self::get_manifest_file(config, &self.game_id);
+        fs::create_dir_all(path.parent().unwrap()).await?;
+        let mut file = fs::File::create(path).await?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    pub async fn remove(config: &Config, game_id: &i64) -> Result<(), Box<dyn Error>> {
+        match fs::remove_file(Self::get_manifest_file(config, game_id)).await {
+            Err(err) if err.kind() != tokio::io::ErrorKind::NotFound => Err(Box::new(err)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete files of the `previous` install in `dir` that this manifest doesn't have, returns how many.
+    /// Files the user added are never in a manifest, so they are kept.
+    pub async fn remove_dropped(&self, previous: &Manifest, dir: &Path) -> Result<usize, Box<dyn Error>> {
+        let current = self.files.iter().map(|file| file.path.as_str()).collect::<HashSet<&str>>();
+
+        let mut removed = 0;
+        for file in previous.files.iter().filter(|file| !current.contains(file.path.as_str())) {
+            let path = dir.join(&file.path);
+            match fs::remove_file(&path).await {
+                Ok(()) => removed += 1,
+                Err(err) if err.kind() == tokio::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Box::new(err)),
+            }
+
+            // Clean up directories the old version left empty, stopping at the first one still in use.
+            let mut parent = path.parent();
+            while let Some(dir_path) = parent.filter(|dir_path| *dir_path != dir) {
+                if fs::remove_dir(dir_path).await.is_err() {
+                    break;
+                }
+                parent = dir_path.parent();
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Compare the files in `dir` against the manifest.
+    pub async fn verify(&self, dir: &Path) -> Result<VerifyReport, Box<dyn Error>> {
+        let mut report = VerifyReport::default();
+
+        let mut on_disk = HashSet::new();
+        if fs::try_exists(dir).await? {
+            on_disk.extend(list_files(dir).await?);
+        }
+
+        for file in &self.files {
+            if !on_disk.remove(&file.path) {
+                report.missing.push(file.path.clone());
+                continue;
+            }
+
+            let full_path = dir.join(&file.path);
+            if fs::metadata(&full_path).await?.len() != file.size || hash_file(&full_path).await? != file.sha256 {
+                report.modified.push(file.path.clone());
+            }
+        }
+
+        report.extra = on_disk.into_iter().collect();
+        report.extra.sort();
+
+        Ok(report)
+    }
+
+}
+
+
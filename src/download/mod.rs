@@ -1,35 +1,44 @@
 
-use std::error::Error;
+use std::{error::Error, path::PathBuf};
+use clap::ValueEnum;
 use console::style;
-use dialoguer::{Confirm, Select};
-use self::{config::Config, library::Library};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use self::{api::ItchClient, config::Config, error::DownloadError, game::{Game, LaunchOptions}, library::{Library, LibraryCollection}, redact::redact};
 
 pub mod config;
+pub mod configure;
 mod utils;
-mod game;
+pub mod game;
 mod library;
 mod api;
 mod downloader;
 mod error;
+mod manifest;
+pub mod prompt;
+pub mod redact;
+pub mod info;
+pub mod jam;
+mod policy;
+pub mod relocate;
+pub mod resolve;
+mod retry;
+pub mod verify;
 
+pub use self::policy::Platform;
 
 
-pub async fn download_and_execute(config: &Config, game_id: i64) -> Result<(), Box<dyn Error>> {
+
+pub async fn download_and_execute(config: &Config, game_id: i64, extra_args: &[String]) -> Result<(), Box<dyn Error>> {
     let mut library = Library::load(config).await?;
 
     // Get game and prompt of install if not already.
     let mut game = if let Some(game) = library.get_game(config, &game_id) {
         game
     } else {
-        println!("{}", style("Game is not installed, do you want to install game?").magenta());
-
-        let confirmation = Confirm::new()
-            .report(false)
-            .interact()?;
-
-        if confirmation {
+        if prompt::confirm(config.prompt, "Game is not installed, do you want to install game?")? {
             println!("{}", style("Downloading game").magenta());
-            library.download_game(config, game_id).await?;
+            library.download_game(config, game_id, true).await?;
             library.get_game(config, &game_id).unwrap()
         } else {
             return Ok(());
@@ -38,14 +47,8 @@ pub async fn download_and_execute(config: &Config, game_id: i64) -> Result<(), B
 
     // Check if game is up to date.
     if !(game.clone().is_latest(config).await?) {
-        println!("{}", style("Do you want to download the latest version of the game?").magenta());
-
-        let confirmation = Confirm::new()
-            .report(false)
-            .interact()?;
-
-        if confirmation {
-            library.download_game(config, game_id).await?;
+        if prompt::confirm(config.prompt, "Do you want to download the latest version of the game?")? {
+            library.download_game(config, game_id, true).await?;
         }
         
         game = library.get_game(config, &game_id).unwrap();
@@ -53,7 +56,7 @@ pub async fn download_and_execute(config: &Config, game_id: i64) -> Result<(), B
 
     // Start game.
     println!("{}", style("Starting game").magenta());
-    game.clone().start(config).await?;
+    game.clone().start(config, extra_args).await?;
 
     Ok(())
 }
@@ -63,28 +66,426 @@ pub async fn download_and_execute(config: &Config, game_id: i64) -> Result<(), B
 pub async fn select_and_play(config: &Config) -> Result<(), Box<dyn Error>> {
     let library = Library::load(config).await?;
 
-    if library.games.len() == 0 {
+    if library.games.is_empty() {
         println!("{}", style("Library has no games").black().on_red());
         return Ok(());
     }
 
-    println!("{}", style("Select a installed game to play:").magenta());
-
-    let selection = Select::new()
-        .report(false)
-        .item(format!("{}", style("none").magenta()))
-        .items(&(library.games.iter().map(|game| {
+    let items = std::iter::once(format!("{}", style("none").magenta()))
+        .chain(library.games.iter().map(|game| {
             format!("{}", style(game.title.clone()).magenta().bright())
-        }).collect::<Vec<String>>()))
-        .default(0)
-        .interact()?;
+        }))
+        .collect::<Vec<String>>();
+
+    let selection = prompt::select(config.prompt, "Select a installed game to play:", &items, 0)?;
 
     if selection == 0 {
         println!("{}", style("No game selected").magenta());
         return Ok(());
     }
 
-    download_and_execute(config, library.games[selection - 1].game_id).await?;
+    download_and_execute(config, library.games[selection - 1].game_id, &[]).await?;
+
+    Ok(())
+}
+
+
+
+/// Change the launch options of an installed game, `clear` resets them first.
+pub async fn set_launch_options(config: &Config, game_id: i64, changes: LaunchOptions, clear: bool) -> Result<(), Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    let mut game = match library.get_game(config, &game_id) {
+        Some(game) => game.clone(),
+        None => return Err(Box::new(DownloadError::GameNotInstalled(game_id))),
+    };
+
+    if clear {
+        game.launch = LaunchOptions::default();
+    }
+    if changes.exe.is_some() {
+        game.launch.exe = changes.exe.filter(|exe| !exe.is_empty());
+    }
+    if changes.cwd.is_some() {
+        game.launch.cwd = changes.cwd.filter(|cwd| !cwd.is_empty());
+    }
+    if !changes.args.is_empty() {
+        game.launch.args = changes.args;
+    }
+    for (key, value) in changes.env {
+        // `KEY=` removes the variable.
+        if value.is_empty() {
+            game.launch.env.remove(&key);
+        } else {
+            game.launch.env.insert(key, value);
+        }
+    }
+
+    library.set_game(config, &game);
+    library.save(config).await?;
+
+    println!("{} {}", style("Launch options of").magenta(), style(&game.title).magenta().bold());
+    if game.launch.is_empty() {
+        println!("  {}", style("none, the executable is found automatically").dim());
+    } else {
+        println!("{}", serde_json::to_string_pretty(&game.launch)?);
+    }
+
+    Ok(())
+}
+
+
+
+pub async fn uninstall(config: &Config, game_id: i64) -> Result<(), Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    let title = match library.get_game(config, &game_id) {
+        Some(game) => game.title.clone(),
+        None => return Err(Box::new(DownloadError::GameNotInstalled(game_id))),
+    };
+
+    if !prompt::confirm(config.prompt, &format!("Do you want to uninstall {}?", title))? {
+        return Ok(());
+    }
+
+    let freed = library.uninstall_game(config, &game_id).await?;
+    println!("{} {} {}", style("Uninstalled").magenta(), style(&title).magenta().bold(), style(format!("freed {}", HumanBytes(freed))).cyan());
+
+    Ok(())
+}
+
+
+
+/// Download & install games without starting them, returns if every game installed successfully.
+/// `pick` asks which upload to install when several qualify, batches leave it to the upload policy.
+pub async fn install(config: &Config, game_ids: &[i64], force: bool, pick: bool) -> Result<bool, Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    let mut results: Vec<(i64, Result<String, String>)> = Vec::new();
+    for game_id in game_ids {
+        if !force {
+            if let Some(game) = library.get_game(config, game_id) {
+                let mut game = game.clone();
+                if game.is_downloaded(config).await? {
+                    println!("{} {}", style(&game.title).magenta().bold(), style("is already installed").magenta());
+                    results.push((*game_id, Ok(game.title)));
+                    continue;
+                }
+            }
+        }
+
+        println!("{} {}", style("Installing game").magenta(), style(game_id).cyan());
+        let result = match library.download_game(config, *game_id, pick).await {
+            Ok(Some(game)) => Ok(game.title.clone()),
+            Ok(None) => Err(DownloadError::GameNotInstalled(*game_id).to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        results.push((*game_id, result));
+    }
+
+    println!();
+    println!("{}", style("Install summary").magenta().bold());
+    for (game_id, result) in &results {
+        match result {
+            Ok(title) => println!("  {} {:>10}  {}", style("ok  ").green(), style(game_id).cyan(), style(title).magenta().bright()),
+            Err(err) => println!("  {} {:>10}  {}", style("fail").red(), style(game_id).cyan(), style(redact(err)).red()),
+        }
+    }
+
+    Ok(results.iter().all(|(_, result)| result.is_ok()))
+}
+
+/// Install every game in a collection, `remember` makes `update --all` install games added to it later.
+pub async fn install_collection(config: &Config, collection_id: i64, force: bool, remember: bool) -> Result<bool, Box<dyn Error>> {
+    let client = ItchClient::new(config, config.profile.as_deref()).await?;
+    let collection = client.collection(&collection_id).await?.collection;
+    let game_ids = client.collection_games(&collection_id).await?
+        .iter()
+        .map(|collection_game| collection_game.game.id)
+        .collect::<Vec<i64>>();
+    println!("{} {} {}", style("Collection").magenta(), style(&collection.title).magenta().bold(), style(format!("({} games)", game_ids.len())).cyan());
+
+    if remember {
+        let mut library = Library::load(config).await?;
+        library.collections.retain(|remembered| remembered.id != collection.id);
+        library.collections.push(LibraryCollection { id: collection.id, title: collection.title.clone() });
+        library.save(config).await?;
+        println!("{}", style("Remembered collection, `update --all` installs games added to it").magenta());
+    }
+
+    install(config, &game_ids, force, false).await
+}
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    /// Every checked game is on the latest version.
+    Current,
+    /// Outdated games were found & all were updated.
+    Updated,
+    /// Outdated games were found but not updated (`--check`).
+    Available,
+    /// Checking or updating at least one game failed.
+    Failed,
+}
+
+impl UpdateOutcome {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            UpdateOutcome::Current | UpdateOutcome::Updated => 0,
+            UpdateOutcome::Failed => 1,
+            UpdateOutcome::Available => 2,
+        }
+    }
+}
+
+pub async fn update(config: &Config, game_ids: &[i64], all: bool, check: bool) -> Result<UpdateOutcome, Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    let games = if all {
+        library.games.clone()
+    } else {
+        let mut games = Vec::new();
+        for game_id in game_ids {
+            match library.get_game(config, game_id) {
+                Some(game) => games.push(game.clone()),
+                None => return Err(Box::new(DownloadError::GameNotInstalled(*game_id))),
+            }
+        }
+        games
+    };
+
+    println!("{}", style(format!("Checking {} game(s) for updates", games.len())).magenta());
+
+    let mut failed = false;
+    let mut outdated: Vec<Game> = Vec::new();
+    for mut game in games {
+        match game.is_latest(config).await {
+            Ok(true) => { },
+            Ok(false) => outdated.push(game),
+            Err(err) => {
+                println!("{} {} {}", style("Failed to check").red(), style(&game.title).red().bold(), style(redact(&err.to_string())).red());
+                failed = true;
+            },
+        }
+    }
+
+    // Games added to remembered collections since they were installed.
+    let mut new_games: Vec<(i64, String, String)> = Vec::new();
+    if all && !library.collections.is_empty() {
+        let client = ItchClient::new(config, config.profile.as_deref()).await?;
+        for collection in library.collections.clone() {
+            match client.collection_games(&collection.id).await {
+                Ok(collection_games) => {
+                    for collection_game in collection_games {
+                        let game_id = collection_game.game.id;
+                        if library.get_game(config, &game_id).is_none() && !new_games.iter().any(|(new_game_id, _, _)| *new_game_id == game_id) {
+                            new_games.push((game_id, collection_game.game.title, collection.title.clone()));
+                        }
+                    }
+                },
+                Err(err) => {
+                    println!("{} {} {}", style("Failed to check collection").red(), style(&collection.title).red().bold(), style(redact(&err.to_string())).red());
+                    failed = true;
+                },
+            }
+        }
+    }
+
+    if outdated.is_empty() && new_games.is_empty() {
+        println!("{}", style("Every game is up to date").magenta());
+        return Ok(if failed { UpdateOutcome::Failed } else { UpdateOutcome::Current });
+    }
+
+    if !outdated.is_empty() {
+        let title_width = outdated.iter().map(|game| game.title.chars().count()).max().unwrap_or(0).max(5);
+        println!("{}", style(format!("{:>10}  {:<title_width$}  {:>10}", "ID", "TITLE", "UPLOAD")).magenta().bold());
+        for game in &outdated {
+            println!("{:>10}  {}  {:>10}",
+                style(game.game_id).cyan(),
+                style(format!("{:<title_width$}", game.title)).magenta().bright(),
+                game.upload_id,
+            );
+        }
+    }
+
+    if !new_games.is_empty() {
+        let title_width = new_games.iter().map(|(_, title, _)| title.chars().count()).max().unwrap_or(0).max(5);
+        println!("{}", style(format!("{:>10}  {:<title_width$}  {}", "ID", "NEW GAME", "COLLECTION")).magenta().bold());
+        for (game_id, title, collection) in &new_games {
+            println!("{:>10}  {}  {}",
+                style(game_id).cyan(),
+                style(format!("{:<title_width$}", title)).magenta().bright(),
+                style(collection).dim(),
+            );
+        }
+    }
+
+    if check {
+        println!("{}", style(format!("{} game(s) can be updated, {} new game(s) in collections", outdated.len(), new_games.len())).magenta());
+        return Ok(if failed { UpdateOutcome::Failed } else { UpdateOutcome::Available });
+    }
+
+    for game in &outdated {
+        println!("{} {}", style("Updating").magenta(), style(&game.title).magenta().bold());
+        if let Err(err) = library.download_game(config, game.game_id, false).await {
+            println!("{} {} {}", style("Failed to update").red(), style(&game.title).red().bold(), style(redact(&err.to_string())).red());
+            failed = true;
+        }
+    }
+
+    for (game_id, title, _) in &new_games {
+        println!("{} {}", style("Installing").magenta(), style(title).magenta().bold());
+        if let Err(err) = library.download_game(config, *game_id, false).await {
+            println!("{} {} {}", style("Failed to install").red(), style(title).red().bold(), style(redact(&err.to_string())).red());
+            failed = true;
+        }
+    }
+
+    Ok(if failed { UpdateOutcome::Failed } else { UpdateOutcome::Updated })
+}
+
+
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ListSort {
+    Id,
+    Title,
+    Size,
+}
+
+#[derive(Serialize)]
+struct ListEntry {
+    game_id: i64,
+    title: String,
+    upload_id: i64,
+    directory: PathBuf,
+    size: u64,
+    profile: Option<String>,
+    jam: Option<String>,
+}
+
+pub async fn list_games(config: &Config, sort: ListSort, filter: Option<&str>, jam: Option<&str>, json: bool) -> Result<(), Box<dyn Error>> {
+    let library = Library::load(config).await?;
+
+    let filter = filter.map(|filter| filter.to_lowercase());
+    let mut entries = Vec::new();
+    for mut game in library.games {
+        if let Some(filter) = &filter {
+            if !game.title.to_lowercase().contains(filter) {
+                continue;
+            }
+        }
+        if let Some(jam) = jam {
+            if !game.jam.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(jam.trim())) {
+                continue;
+            }
+        }
+
+        entries.push(ListEntry {
+            game_id: game.game_id,
+            upload_id: game.upload_id,
+            directory: game.path(config),
+            size: game.size(config).await?,
+            title: game.title,
+            profile: game.profile,
+            jam: game.jam,
+        });
+    }
+
+    match sort {
+        ListSort::Id => entries.sort_by_key(|entry| entry.game_id),
+        ListSort::Title => entries.sort_by_key(|entry| entry.title.to_lowercase()),
+        ListSort::Size => entries.sort_by_key(|entry| std::cmp::Reverse(entry.size)),
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("{}", style("No games found").black().on_red());
+        return Ok(());
+    }
+
+    let title_width = entries.iter().map(|entry| entry.title.chars().count()).max().unwrap_or(0).max(5);
+    println!("{}", style(format!("{:>10}  {:<title_width$}  {:>10}  {:>10}  {}", "ID", "TITLE", "UPLOAD", "SIZE", "DIRECTORY")).magenta().bold());
+    for entry in &entries {
+        println!("{:>10}  {}  {:>10}  {:>10}  {}",
+            style(entry.game_id).cyan(),
+            style(format!("{:<title_width$}", entry.title)).magenta().bright(),
+            entry.upload_id,
+            HumanBytes(entry.size).to_string(),
+            style(entry.directory.display()).dim(),
+        );
+    }
+
+    Ok(())
+}
+
+
+
+#[derive(Serialize)]
+struct OwnedEntry {
+    game_id: i64,
+    title: String,
+    url: String,
+    download_key_id: i64,
+    purchased_at: String,
+    installed: bool,
+    downloaded: bool,
+}
+
+/// List every game bought or claimed with the account, with its install state.
+pub async fn list_owned(config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+    let client = ItchClient::new(config, config.profile.as_deref()).await?;
+
+    let mut entries = Vec::new();
+    for owned_key in client.owned_keys().await? {
+        let (installed, downloaded) = match library.get_game(config, &owned_key.game_id) {
+            Some(game) => (true, game.clone().is_downloaded(config).await?),
+            None => (false, false),
+        };
+        entries.push(OwnedEntry {
+            game_id: owned_key.game_id,
+            title: owned_key.game.title,
+            url: owned_key.game.url,
+            download_key_id: owned_key.id,
+            purchased_at: owned_key.created_at,
+            installed,
+            downloaded,
+        });
+    }
+    entries.sort_by_key(|entry| entry.title.to_lowercase());
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("{}", style("No owned games found").black().on_red());
+        return Ok(());
+    }
+
+    let title_width = entries.iter().map(|entry| entry.title.chars().count()).max().unwrap_or(0).max(5);
+    println!("{}", style(format!("{:>10}  {:<title_width$}  {:<13}  {}", "ID", "TITLE", "INSTALLED", "URL")).magenta().bold());
+    for entry in &entries {
+        let installed = match (entry.installed, entry.downloaded) {
+            (true, true) => style("yes".to_string()).green(),
+            (true, false) => style("files missing".to_string()).red(),
+            (false, _) => style("no".to_string()).dim(),
+        };
+        println!("{:>10}  {}  {:<13}  {}",
+            style(entry.game_id).cyan(),
+            style(format!("{:<title_width$}", entry.title)).magenta().bright(),
+            installed,
+            style(&entry.url).dim(),
+        );
+    }
 
     Ok(())
 }
@@ -0,0 +1,535 @@
+
+use std::cmp::Reverse;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use super::{api::GameUpload, config::Config};
+
+
+
+/// Operating system an upload is built for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Windows,
+    Linux,
+    Osx,
+}
+
+impl Platform {
+    /// Platform this build runs on.
+    pub fn host() -> Self {
+        if cfg!(target_os = "linux") {
+            Platform::Linux
+        } else if cfg!(target_os = "macos") {
+            Platform::Osx
+        } else {
+            Platform::Windows
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Windows => "windows",
+            Platform::Linux => "linux",
+            Platform::Osx => "osx",
+        }
+    }
+
+    pub fn supported_by(&self, game_upload: &GameUpload) -> bool {
+        match self {
+            Platform::Windows => game_upload.p_windows,
+            Platform::Linux => game_upload.p_linux,
+            Platform::Osx => game_upload.p_osx,
+        }
+    }
+}
+
+
+
+/// What an upload rule does to the uploads it matches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Rank matching uploads above the others.
+    #[default]
+    Prefer,
+    /// Rank matching uploads below the others.
+    Avoid,
+    /// Never pick matching uploads.
+    Exclude,
+}
+
+/// A rule from `upload_rules`, matches uploads whose channel & name match its globs.
+/// Globs ignore case, `*` matches anything & `?` any one character.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UploadRule {
+    /// Glob for the channel name, like `*-64`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Glob for the display name or filename.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub action: RuleAction,
+}
+
+impl UploadRule {
+    /// Rules without a glob match nothing.
+    pub fn matches(&self, game_upload: &GameUpload) -> bool {
+        if self.channel.is_none() && self.name.is_none() {
+            return false;
+        }
+        let channel = match &self.channel {
+            Some(pattern) => game_upload.channel_name.as_deref().is_some_and(|channel| glob_match(pattern, channel)),
+            None => true,
+        };
+        let name = match &self.name {
+            Some(pattern) => glob_match(pattern, game_upload.name()) || glob_match(pattern, &game_upload.filename),
+            None => true,
+        };
+        channel && name
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(channel) = &self.channel {
+            parts.push(format!("channel \"{}\"", channel));
+        }
+        if let Some(name) = &self.name {
+            parts.push(format!("name \"{}\"", name));
+        }
+        parts.join(" & ")
+    }
+}
+
+/// If `text` matches the glob `pattern`, ignoring case.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<char>>();
+    let text = text.to_lowercase().chars().collect::<Vec<char>>();
+
+    let (mut p, mut t) = (0, 0);
+    // On a mismatch, let the last `*` swallow one more character & try again.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+
+
+/// Upload an installed game keeps updating to, after it was picked from several that qualify.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadTrack {
+    /// Whichever upload is on the channel, channels keep their name across new builds.
+    Channel(String),
+    /// The upload itself, for uploads without a channel.
+    Upload(i64),
+}
+
+impl UploadTrack {
+    /// Track to follow after picking `game_upload`.
+    pub fn of(game_upload: &GameUpload) -> Self {
+        match &game_upload.channel_name {
+            Some(channel) => UploadTrack::Channel(channel.clone()),
+            None => UploadTrack::Upload(game_upload.id),
+        }
+    }
+
+    pub fn matches(&self, game_upload: &GameUpload) -> bool {
+        match self {
+            UploadTrack::Channel(channel) => game_upload.channel_name.as_ref() == Some(channel),
+            UploadTrack::Upload(upload_id) => game_upload.id == *upload_id,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            UploadTrack::Channel(channel) => format!("channel \"{}\"", channel),
+            UploadTrack::Upload(upload_id) => format!("upload {}", upload_id),
+        }
+    }
+}
+
+
+
+/// An upload that qualifies & the platform it gets installed for.
+#[derive(Clone, Copy)]
+pub struct Candidate<'a> {
+    pub upload: &'a GameUpload,
+    pub platform: Platform,
+}
+
+/// Decides which upload of a game gets installed.
+///
+/// Uploads qualify when they support one of the target platforms & are hosted on itch.io (or mega),
+/// minus uploads excluded by a rule, and minus demos & preorders when a full version qualifies.
+/// They are then ranked by, in order: platform preference, game files over extras like soundtracks,
+/// upload rules, position on the game page, most recently updated.
+/// A followed track overrides the ranking while one of its uploads qualifies.
+pub struct UploadPolicy<'a> {
+    platforms: Vec<Platform>,
+    rules: &'a [UploadRule],
+    track: Option<&'a UploadTrack>,
+}
+
+impl<'a> UploadPolicy<'a> {
+
+    /// `installed` is the platform of an installed game, so it can keep updating when there is no better upload.
+    pub fn new(config: &'a Config, installed: Option<Platform>) -> Self {
+        let platforms = match config.platform {
+            Some(platform) => vec![ platform ],
+            None => {
+                let host = Platform::host();
+                let mut platforms = vec![ host ];
+                if host != Platform::Windows && config.windows_runner.is_some() {
+                    platforms.push(Platform::Windows);
+                }
+                if let Some(installed) = installed {
+                    if !platforms.contains(&installed) {
+                        platforms.push(installed);
+                    }
+                }
+                platforms
+            },
+        };
+
+        Self { platforms, rules: &config.upload_rules, track: None }
+    }
+
+    /// Follow the track an installed game was put on.
+    pub fn follow(mut self, track: Option<&'a UploadTrack>) -> Self {
+        self.track = track;
+        self
+    }
+
+    /// The qualifying upload on the followed track.
+    pub fn on_track<'u>(&self, candidates: &[Candidate<'u>]) -> Option<Candidate<'u>> {
+        let track = self.track?;
+        candidates.iter().find(|candidate| track.matches(candidate.upload)).copied()
+    }
+
+    pub fn platform_names(&self) -> String {
+        self.platforms.iter().map(Platform::name).collect::<Vec<&str>>().join(" or ")
+    }
+
+    /// If builds listed under a platform name (`windows`, `linux`, `osx`) can be picked.
+    pub fn targets(&self, name: &str) -> bool {
+        self.platforms.iter().any(|platform| platform.name() == name)
+    }
+
+    fn is_hosted(game_upload: &GameUpload) -> bool {
+        match &game_upload.host {
+            Some(host) => host == "mega.nz" || host == "mega.co.nz",
+            None => true,
+        }
+    }
+
+    fn is_full(game_upload: &GameUpload) -> bool {
+        !game_upload.demo && !game_upload.preorder
+    }
+
+    /// Preferred rules matched minus avoided rules matched.
+    fn score(&self, game_upload: &GameUpload) -> i64 {
+        self.rules.iter()
+            .filter(|rule| rule.matches(game_upload))
+            .map(|rule| match rule.action {
+                RuleAction::Prefer => 1,
+                RuleAction::Avoid => -1,
+                RuleAction::Exclude => 0,
+            })
+            .sum()
+    }
+
+    fn rank_key<'u>(&self, candidate: &Candidate<'u>) -> (usize, bool, Reverse<i64>, i64, Reverse<&'u str>) {
+        let game_upload = candidate.upload;
+        (
+            self.platforms.iter().position(|platform| *platform == candidate.platform).unwrap_or(usize::MAX),
+            game_upload.r#type != "default",
+            Reverse(self.score(game_upload)),
+            game_upload.position,
+            Reverse(game_upload.updated_at.as_str()),
+        )
+    }
+
+    /// Every qualifying upload, best first, with notes on what was left out. Or why no upload qualifies.
+    pub fn rank<'u>(&self, uploads: &'u [GameUpload]) -> Result<(Vec<Candidate<'u>>, Vec<String>), String> {
+        let mut candidates = uploads.iter()
+            .filter_map(|game_upload| {
+                let platform = self.platforms.iter().find(|platform| platform.supported_by(game_upload))?;
+                Some(Candidate { upload: game_upload, platform: *platform })
+            })
+            .collect::<Vec<Candidate>>();
+        if candidates.is_empty() {
+            return Err(format!("no upload supports {}", self.platform_names()));
+        }
+
+        candidates.retain(|candidate| Self::is_hosted(candidate.upload));
+        if candidates.is_empty() {
+            return Err(format!("no {} upload is hosted on a supported host", self.platform_names()));
+        }
+
+        let mut notes = Vec::new();
+        let count = candidates.len();
+        candidates.retain(|candidate| !self.rules.iter().any(|rule| rule.action == RuleAction::Exclude && rule.matches(candidate.upload)));
+        if candidates.is_empty() {
+            return Err(format!("every {} upload is excluded by upload_rules", self.platform_names()));
+        }
+        if candidates.len() < count {
+            notes.push(format!("{} excluded by upload_rules", count - candidates.len()));
+        }
+
+        if candidates.iter().any(|candidate| Self::is_full(candidate.upload)) {
+            let count = candidates.len();
+            candidates.retain(|candidate| Self::is_full(candidate.upload));
+            if candidates.len() < count {
+                notes.push(format!("{} demo or preorder left out", count - candidates.len()));
+            }
+        }
+
+        candidates.sort_by(|a, b| self.rank_key(a).cmp(&self.rank_key(b)).then(a.upload.id.cmp(&b.upload.id)));
+        Ok((candidates, notes))
+    }
+
+    /// Why `best` ranks above `next`.
+    fn deciding(&self, best: &Candidate, next: &Candidate) -> String {
+        let (best_key, next_key) = (self.rank_key(best), self.rank_key(next));
+        if best_key.0 != next_key.0 {
+            format!("{} preferred over {}", best.platform.name(), next.platform.name())
+        } else if best_key.1 != next_key.1 {
+            format!("game files preferred over {}", next.upload.r#type)
+        } else if best_key.2 != next_key.2 {
+            let rule = self.rules.iter()
+                .find(|rule| rule.matches(best.upload) != rule.matches(next.upload))
+                .map(UploadRule::describe)
+                .unwrap_or_default();
+            format!("upload rule {}", rule)
+        } else if best_key.3 != next_key.3 {
+            "first on the game page".into()
+        } else if best_key.4 != next_key.4 {
+            "most recently updated".into()
+        } else {
+            "lowest upload id".into()
+        }
+    }
+
+    /// The upload to install & why it was picked, or why no upload can be installed.
+    pub fn pick<'u>(&self, uploads: &'u [GameUpload]) -> Result<(Candidate<'u>, String), String> {
+        let (candidates, mut notes) = self.rank(uploads)?;
+        if let (Some(track), Some(candidate)) = (self.track, self.on_track(&candidates)) {
+            return Ok((candidate, format!("follows {} of the installed game", track.describe())));
+        }
+
+        let best = candidates[0];
+        let reason = match candidates.get(1) {
+            None => format!("only qualifying upload, for {}", best.platform.name()),
+            Some(next) => format!("best of {} qualifying uploads, {}", candidates.len(), self.deciding(&best, next)),
+        };
+        notes.insert(0, reason);
+        Ok((best, notes.join(", ")))
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(id: i64, channel: &str, position: i64) -> GameUpload {
+        GameUpload {
+            id,
+            build_id: None,
+            position,
+            filename: format!("{}.zip", channel),
+            size: Some(1024),
+            demo: false,
+            preorder: false,
+            storage: "hosted".into(),
+            host: None,
+            created_at: "2024-01-01T00:00:00Z".into(),
+            updated_at: "2024-01-01T00:00:00Z".into(),
+            p_windows: true,
+            p_osx: false,
+            p_linux: false,
+            p_android: false,
+            build: None,
+            channel_name: Some(channel.into()),
+            r#type: "default".into(),
+            game_id: 1,
+            display_name: None,
+        }
+    }
+
+    fn rule(channel: Option<&str>, name: Option<&str>, action: RuleAction) -> UploadRule {
+        UploadRule { channel: channel.map(String::from), name: name.map(String::from), action }
+    }
+
+    fn policy<'a>(platforms: &[Platform], rules: &'a [UploadRule]) -> UploadPolicy<'a> {
+        UploadPolicy { platforms: platforms.to_vec(), rules, track: None }
+    }
+
+    fn picked(policy: &UploadPolicy, uploads: &[GameUpload]) -> i64 {
+        policy.pick(uploads).unwrap().0.upload.id
+    }
+
+    fn refused(policy: &UploadPolicy, uploads: &[GameUpload]) -> String {
+        match policy.pick(uploads) {
+            Ok((candidate, _)) => panic!("picked upload {}", candidate.upload.id),
+            Err(reason) => reason,
+        }
+    }
+
+    #[test]
+    fn glob_star_matches_any_run() {
+        assert!(glob_match("*-64", "windows-64"));
+        assert!(!glob_match("*-64", "windows-32"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "abc"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+        assert!(glob_match("*soundtrack*", "Game Soundtrack (FLAC)"));
+        assert!(glob_match("win**", "win"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_one_character() {
+        assert!(glob_match("win??", "win64"));
+        assert!(!glob_match("win??", "win6"));
+        assert!(!glob_match("win??", "win645"));
+        assert!(glob_match("?*", "x"));
+        assert!(!glob_match("?*", ""));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn glob_ignores_case() {
+        assert!(glob_match("*-X64", "Windows-x64"));
+    }
+
+    #[test]
+    fn first_position_wins_without_rules() {
+        let uploads = [upload(1, "windows-64", 1), upload(2, "windows-32", 0)];
+        assert_eq!(picked(&policy(&[Platform::Windows], &[]), &uploads), 2);
+    }
+
+    #[test]
+    fn prefer_rule_beats_position() {
+        let rules = [rule(Some("*-64"), None, RuleAction::Prefer)];
+        let uploads = [upload(1, "windows-32", 0), upload(2, "windows-64", 1)];
+        let (candidate, reason) = policy(&[Platform::Windows], &rules).pick(&uploads).unwrap();
+        assert_eq!(candidate.upload.id, 2);
+        assert!(reason.contains("upload rule channel \"*-64\""), "{}", reason);
+    }
+
+    #[test]
+    fn avoid_rule_ranks_below_others() {
+        let rules = [rule(Some("*-32"), None, RuleAction::Avoid)];
+        let uploads = [upload(1, "windows-32", 0), upload(2, "windows-64", 1)];
+        assert_eq!(picked(&policy(&[Platform::Windows], &rules), &uploads), 2);
+    }
+
+    #[test]
+    fn exclude_rule_removes_uploads() {
+        let rules = [rule(None, Some("*soundtrack*"), RuleAction::Exclude)];
+        let mut soundtrack = upload(1, "ost", 0);
+        soundtrack.display_name = Some("Soundtrack".into());
+        let uploads = [soundtrack, upload(2, "windows", 1)];
+        let policy = policy(&[Platform::Windows], &rules);
+
+        let (candidates, notes) = policy.rank(&uploads).unwrap();
+        assert_eq!(candidates.iter().map(|candidate| candidate.upload.id).collect::<Vec<i64>>(), vec![ 2 ]);
+        assert_eq!(notes, vec![ "1 excluded by upload_rules".to_string() ]);
+
+        assert!(refused(&policy, &uploads[..1]).contains("excluded by upload_rules"));
+    }
+
+    #[test]
+    fn demos_and_preorders_are_left_out_when_a_full_version_exists() {
+        let mut demo = upload(1, "demo", 0);
+        demo.demo = true;
+        let mut preorder = upload(2, "preorder", 1);
+        preorder.preorder = true;
+        let uploads = [demo, preorder, upload(3, "windows", 2)];
+        let (candidate, reason) = policy(&[Platform::Windows], &[]).pick(&uploads).unwrap();
+        assert_eq!(candidate.upload.id, 3);
+        assert!(reason.contains("2 demo or preorder left out"), "{}", reason);
+    }
+
+    #[test]
+    fn demo_is_picked_when_it_is_the_only_version() {
+        let mut demo = upload(1, "demo", 0);
+        demo.demo = true;
+        let mut linux = upload(2, "linux", 1);
+        (linux.p_windows, linux.p_linux) = (false, true);
+        assert_eq!(picked(&policy(&[Platform::Windows], &[]), &[demo, linux]), 1);
+    }
+
+    #[test]
+    fn platform_order_beats_position() {
+        let mut linux = upload(2, "linux", 1);
+        (linux.p_windows, linux.p_linux) = (false, true);
+        let uploads = [upload(1, "windows", 0), linux];
+
+        let (candidate, reason) = policy(&[Platform::Linux, Platform::Windows], &[]).pick(&uploads).unwrap();
+        assert_eq!((candidate.upload.id, candidate.platform), (2, Platform::Linux));
+        assert!(reason.contains("linux preferred over windows"), "{}", reason);
+
+        let (candidate, _) = policy(&[Platform::Windows, Platform::Linux], &[]).pick(&uploads).unwrap();
+        assert_eq!((candidate.upload.id, candidate.platform), (1, Platform::Windows));
+    }
+
+    #[test]
+    fn unsupported_platform_and_host_fail() {
+        let uploads = [upload(1, "windows", 0)];
+        assert_eq!(refused(&policy(&[Platform::Osx], &[]), &uploads), "no upload supports osx");
+
+        let mut external = upload(2, "windows", 0);
+        external.host = Some("drive.google.com".into());
+        assert_eq!(refused(&policy(&[Platform::Windows], &[]), &[external]), "no windows upload is hosted on a supported host");
+    }
+
+    #[test]
+    fn game_files_rank_above_extras() {
+        let mut soundtrack = upload(1, "ost", 0);
+        soundtrack.r#type = "soundtrack".into();
+        assert_eq!(picked(&policy(&[Platform::Windows], &[]), &[soundtrack, upload(2, "windows", 1)]), 2);
+    }
+
+    #[test]
+    fn ties_go_to_most_recently_updated_then_lowest_id() {
+        let mut newer = upload(3, "windows-new", 0);
+        newer.updated_at = "2024-06-01T00:00:00Z".into();
+        let uploads = [upload(1, "windows-old", 0), newer];
+        assert_eq!(picked(&policy(&[Platform::Windows], &[]), &uploads), 3);
+
+        let uploads = [upload(5, "a", 0), upload(4, "b", 0)];
+        let (_, reason) = policy(&[Platform::Windows], &[]).pick(&uploads).unwrap();
+        assert_eq!(picked(&policy(&[Platform::Windows], &[]), &uploads), 4);
+        assert!(reason.contains("lowest upload id"), "{}", reason);
+    }
+
+    #[test]
+    fn followed_track_overrides_ranking() {
+        let track = UploadTrack::Channel("windows-32".into());
+        let uploads = [upload(1, "windows-64", 0), upload(2, "windows-32", 1)];
+        let policy = policy(&[Platform::Windows], &[]).follow(Some(&track));
+        assert_eq!(picked(&policy, &uploads), 2);
+        // A track with no qualifying upload falls back to the ranking.
+        assert_eq!(picked(&policy, &uploads[..1]), 1);
+    }
+}
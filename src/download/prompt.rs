@@ -0,0 +1,100 @@
+
+use std::{error::Error, io::IsTerminal};
+use console::{style, Term};
+use dialoguer::{Confirm, Input, Password, Select};
+use serde::{Deserialize, Serialize};
+use super::error::DownloadError;
+
+
+
+/// How prompts get answered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PromptMode {
+    /// Ask the user, fails if there is no terminal to ask on.
+    #[default]
+    Ask,
+    /// Answer yes to every confirmation.
+    Yes,
+    /// Answer no to every confirmation.
+    No,
+    /// Never prompt, fail instead.
+    NoInput,
+}
+
+
+
+/// Prompts read answers from stdin & draw on stderr, so both have to be a terminal.
+fn can_prompt() -> bool {
+    std::io::stdin().is_terminal() && Term::stderr().is_term()
+}
+
+pub fn confirm(mode: PromptMode, message: &str) -> Result<bool, Box<dyn Error>> {
+    println!("{}", style(message).magenta());
+
+    match mode {
+        PromptMode::Yes => {
+            println!("{}", style("yes (answered automatically)").dim());
+            Ok(true)
+        },
+        PromptMode::No => {
+            println!("{}", style("no (answered automatically)").dim());
+            Ok(false)
+        },
+        PromptMode::NoInput => Err(Box::new(DownloadError::PromptUnavailable(message.into()))),
+        PromptMode::Ask => {
+            if !can_prompt() {
+                return Err(Box::new(DownloadError::PromptUnavailable(message.into())));
+            }
+
+            Ok(Confirm::new()
+                .report(false)
+                .interact()?)
+        },
+    }
+}
+
+/// If [`select`] can ask, so callers can fall back to an automatic choice instead.
+pub fn can_select(mode: PromptMode) -> bool {
+    mode == PromptMode::Ask && can_prompt()
+}
+
+/// Selection can't be answered automatically, so anything but [`PromptMode::Ask`] fails.
+pub fn select(mode: PromptMode, message: &str, items: &[String], default: usize) -> Result<usize, Box<dyn Error>> {
+    if !can_select(mode) {
+        return Err(Box::new(DownloadError::SelectUnavailable(message.into())));
+    }
+
+    println!("{}", style(message).magenta());
+
+    Ok(Select::new()
+        .report(false)
+        .items(items)
+        .default(default)
+        .interact()?)
+}
+
+pub fn input(mode: PromptMode, message: &str, default: Option<&str>) -> Result<String, Box<dyn Error>> {
+    if mode != PromptMode::Ask || !can_prompt() {
+        return Err(Box::new(DownloadError::PromptUnavailable(message.into())));
+    }
+
+    let mut input = Input::<String>::new()
+        .with_prompt(format!("{}", style(message).magenta()));
+    if let Some(default) = default {
+        input = input.default(default.into());
+    }
+    Ok(input.interact_text()?)
+}
+
+pub fn password(mode: PromptMode, message: &str) -> Result<String, Box<dyn Error>> {
+    if mode != PromptMode::Ask || !can_prompt() {
+        return Err(Box::new(DownloadError::PromptUnavailable(message.into())));
+    }
+
+    Ok(Password::new()
+        .with_prompt(format!("{}", style(message).magenta()))
+        .interact()?)
+}
+
+
@@ -0,0 +1,127 @@
+
+use std::sync::Mutex;
+
+
+
+/// Every API key in use, so they can be scrubbed from anything printed.
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub const REDACTED: &str = "********";
+
+
+
+/// Register a secret that must never be printed.
+/// Very short values would mangle unrelated text & can't be real API keys, so they are skipped.
+pub fn add_secret(secret: &str) {
+    if secret.len() < 8 {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.into());
+    }
+}
+
+/// Hide all but the last 4 characters of an API key, short keys are hidden entirely.
+pub fn redact_key(api_key: &str) -> String {
+    if api_key.is_empty() {
+        return "(not set)".into();
+    }
+    let count = api_key.chars().count();
+    if count < 12 {
+        return REDACTED.into();
+    }
+    format!("{}{}", REDACTED, api_key.chars().skip(count - 4).collect::<String>())
+}
+
+/// Signed download URLs carry their credentials in the query, keep only the parameter names.
+pub fn redact_url(url: &reqwest::Url) -> reqwest::Url {
+    let mut url = url.clone();
+    if url.query().is_some() {
+        let names = url.query_pairs().map(|(name, _)| name.into_owned()).collect::<Vec<String>>();
+        url.query_pairs_mut().clear().extend_pairs(names.iter().map(|name| (name, REDACTED)));
+    }
+    url.set_fragment(None);
+    let _ = url.set_password(None);
+    url
+}
+
+/// Remove registered secrets & signed URL queries from text.
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        text = text.replace(secret, REDACTED);
+    }
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(start) = ["http://", "https://"].iter().filter_map(|scheme| rest.find(scheme)).min() {
+        redacted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c: char| c.is_whitespace() || c == ')' || c == '"' || c == '\'').unwrap_or(rest.len());
+        match reqwest::Url::parse(&rest[..end]) {
+            Ok(url) => redacted.push_str(redact_url(&url).as_str()),
+            Err(_) => redacted.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+
+    redacted
+}
+
+/// reqwest errors include the request URL, which may be a signed download URL.
+pub fn redact_error(err: reqwest::Error) -> reqwest::Error {
+    match err.url().map(redact_url) {
+        Some(url) => err.with_url(url),
+        None => err,
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::error::DownloadError;
+
+    #[test]
+    fn registered_secret_is_removed_from_errors() {
+        add_secret("registered-secret-1234");
+        let err: Box<dyn std::error::Error> = DownloadError::Api("game/1?api_key=registered-secret-1234".into(), "invalid key".into()).into();
+        assert_eq!(redact(&err.to_string()), "itch.io API error for game/1?api_key=********: invalid key.");
+    }
+
+    #[test]
+    fn api_key_keeps_only_last_characters() {
+        assert_eq!(redact_key(""), "(not set)");
+        assert_eq!(redact_key("short-key"), "********");
+        assert_eq!(redact_key("abcdefgh12345678"), "********5678");
+    }
+
+    #[test]
+    fn signed_url_query_is_scrubbed() {
+        assert_eq!(
+            redact("Download of https://files.itch.zone/game.zip?Signature=abc&Expires=123#part failed"),
+            "Download of https://files.itch.zone/game.zip?Signature=********&Expires=******** failed",
+        );
+    }
+
+    #[test]
+    fn url_ends_before_closing_paren_or_quote() {
+        assert_eq!(redact("failed (https://a.test/f?token=abc)"), "failed (https://a.test/f?token=********)");
+        assert_eq!(redact("url \"https://a.test/f?token=abc\" invalid"), "url \"https://a.test/f?token=********\" invalid");
+    }
+
+    #[test]
+    fn url_without_query_is_kept() {
+        assert_eq!(redact("see https://itch.io/api/1/game/1"), "see https://itch.io/api/1/game/1");
+    }
+
+    #[test]
+    fn short_secrets_are_ignored() {
+        add_secret("x");
+        add_secret("shortke");
+        assert_eq!(redact("x marks the shortke spot"), "x marks the shortke spot");
+    }
+}
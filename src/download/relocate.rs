@@ -0,0 +1,151 @@
+
+use std::{error::Error, path::{Path, PathBuf}};
+use console::style;
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt};
+use super::{config::Config, error::DownloadError, library::Library, utils::{dir_size, move_dir}};
+
+
+
+/// Written to the old `games_dir` while a move is in progress, so an interrupted move can be resumed.
+pub const MOVE_JOURNAL: &str = "library_move.json";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MoveJournal {
+    target: PathBuf,
+}
+
+
+
+#[cfg(unix)]
+async fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    // The target may not exist yet, so check the closest existing ancestor.
+    let mut b = b.to_path_buf();
+    while !fs::try_exists(&b).await.unwrap_or(false) {
+        if !b.pop() {
+            return None;
+        }
+    }
+
+    Some(fs::metadata(a).await.ok()?.dev() == fs::metadata(&b).await.ok()?.dev())
+}
+
+#[cfg(not(unix))]
+async fn same_filesystem(_a: &Path, _b: &Path) -> Option<bool> {
+    None
+}
+
+async fn write_journal(path: &Path, journal: &MoveJournal) -> Result<(), Box<dyn Error>> {
+    let mut file = fs::File::create(path).await?;
+    file.write_all(serde_json::to_string_pretty(journal)?.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), Box<dyn Error>> {
+    match fs::remove_file(path).await {
+        Err(err) if err.kind() != tokio::io::ErrorKind::NotFound => Err(Box::new(err)),
+        _ => Ok(()),
+    }
+}
+
+
+
+/// Move every game in the library to `new_dir` & point the config at it.
+pub async fn move_library(config: &Config, new_dir: &Path, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    // `games_dir` is relative when the config path is, compare both as absolute paths.
+    let old_dir = std::path::absolute(&config.games_dir)?;
+    let new_dir = std::path::absolute(new_dir)?;
+
+    if new_dir == old_dir {
+        return Err(Box::new(DownloadError::LibraryMove("library is already in that directory".into())));
+    }
+    if new_dir.starts_with(&old_dir) {
+        return Err(Box::new(DownloadError::LibraryMove("can't move the library into itself".into())));
+    }
+
+    let journal_path = old_dir.join(MOVE_JOURNAL);
+    if let Ok(str) = fs::read_to_string(&journal_path).await {
+        let journal: MoveJournal = serde_json::from_str(&str)?;
+        if journal.target != new_dir {
+            return Err(Box::new(DownloadError::LibraryMove(format!("a move to {} was interrupted, run it again to finish it", journal.target.display()))));
+        }
+        println!("{}", style("Resuming interrupted library move").magenta());
+    }
+
+    let mut library = Library::load(config).await?;
+
+    // Plan the move.
+    let mut total_size = 0;
+    let mut moves: Vec<(String, PathBuf, PathBuf)> = Vec::new();
+    for game in &mut library.games {
+        let src = game.path(config);
+        let dst = new_dir.join(&game.directory);
+        if game.is_downloaded(config).await? {
+            total_size += dir_size(&src).await?;
+            moves.push((game.title.clone(), src, dst));
+        } else if !fs::try_exists(&dst).await? {
+            println!("{} {}", style(&game.title).magenta().bold(), style("has no files to move").yellow());
+        }
+    }
+
+    let manifests_src = old_dir.join("manifests");
+    if fs::try_exists(&manifests_src).await? {
+        total_size += dir_size(&manifests_src).await?;
+    }
+
+    if dry_run {
+        println!("{} {} {} {}", style("Would move library from").magenta(), style(old_dir.display()).cyan(), style("to").magenta(), style(new_dir.display()).cyan());
+        for (title, src, _) in &moves {
+            println!("  {:>10}  {}", HumanBytes(dir_size(src).await?).to_string(), style(title).magenta().bright());
+        }
+        println!("{} {}", style("Total size").magenta(), style(HumanBytes(total_size)).cyan());
+        match same_filesystem(&old_dir, &new_dir).await {
+            Some(true) => println!("{}", style("Same filesystem, games are renamed in place & need no extra space").magenta()),
+            Some(false) => println!("{} {}", style("Different filesystem, free space needed at target:").magenta(), style(HumanBytes(total_size)).cyan()),
+            None => println!("{} {}", style("Free space needed at target, if on a different filesystem:").magenta(), style(HumanBytes(total_size)).cyan()),
+        }
+        return Ok(());
+    }
+
+    // Refuse before anything is moved, so a conflict never leaves a half moved library.
+    for (_, _, dst) in &moves {
+        if fs::try_exists(dst).await? {
+            return Err(Box::new(DownloadError::LibraryMove(format!("{} already exists", dst.display()))));
+        }
+    }
+
+    fs::create_dir_all(&new_dir).await?;
+    write_journal(&journal_path, &MoveJournal { target: new_dir.clone() }).await?;
+
+    // Move game files, each game directory is moved as a whole so it is either in the old or new location.
+    for (title, src, dst) in &moves {
+        println!("{} {}", style("Moving").magenta(), style(title).magenta().bold());
+        move_dir(src, dst).await?;
+    }
+
+    if fs::try_exists(&manifests_src).await? {
+        move_dir(&manifests_src, &new_dir.join("manifests")).await?;
+    }
+
+    // Write library to the new location, then switch the config over.
+    let mut new_config = config.clone();
+    new_config.games_dir = new_dir.clone();
+    library.save(&new_config).await?;
+    config.set_games_dir(&new_dir).await?;
+
+    // Old location is no longer used, clean up.
+    remove_if_exists(&old_dir.join("library_info.json")).await?;
+    remove_if_exists(&journal_path).await?;
+    let _ = fs::remove_dir(old_dir.join("temp")).await;
+    let _ = fs::remove_dir(&old_dir).await;
+
+    println!("{} {} {}", style("Moved library to").magenta(), style(new_dir.display()).cyan(), style(format!("({})", HumanBytes(total_size))).cyan());
+
+    Ok(())
+}
+
+
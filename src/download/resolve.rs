@@ -0,0 +1,103 @@
+
+use std::error::Error;
+use super::{api::ItchClient, config::Config, error::DownloadError, library::Library};
+
+
+
+/// Resolve a game argument to a game id.
+///
+/// Accepts a game id, an itch.io page URL, an `author/game` slug,
+/// or (part of) the title of an installed game.
+pub async fn resolve_game_id(config: &Config, library: &Library, game: &str) -> Result<i64, Box<dyn Error>> {
+    let game = game.trim();
+
+    if let Ok(game_id) = game.parse::<i64>() {
+        return Ok(game_id);
+    }
+
+    if game.starts_with("https://") || game.starts_with("http://") {
+        return Ok(ItchClient::anonymous(config)?.page_data(game).await?.id);
+    }
+
+    let query = game.to_lowercase();
+
+    // Exact title match wins over everything else.
+    if let Some(found) = library.games.iter().find(|g| g.title.to_lowercase() == query) {
+        return Ok(found.game_id);
+    }
+
+    if let [author, slug] = game.split('/').collect::<Vec<&str>>()[..] {
+        if is_slug_part(author) && is_slug_part(slug) {
+            return Ok(ItchClient::anonymous(config)?.page_data(&format!("https://{}.itch.io/{}", author, slug)).await?.id);
+        }
+    }
+
+    let mut candidates = library.games.iter()
+        .filter(|g| g.title.to_lowercase().contains(&query))
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        candidates = library.games.iter()
+            .filter(|g| is_subsequence(&query, &g.title.to_lowercase()))
+            .collect::<Vec<_>>();
+    }
+
+    match candidates[..] {
+        [] => Err(Box::new(DownloadError::GameNotFound(game.into()))),
+        [found] => Ok(found.game_id),
+        _ => Err(Box::new(DownloadError::GameAmbiguous(
+            game.into(),
+            candidates.iter().map(|g| (g.game_id, g.title.clone())).collect(),
+        ))),
+    }
+}
+
+pub async fn resolve_game(config: &Config, game: &str) -> Result<i64, Box<dyn Error>> {
+    let library = Library::load(config).await?;
+    resolve_game_id(config, &library, game).await
+}
+
+pub async fn resolve_game_ids(config: &Config, games: &[String]) -> Result<Vec<i64>, Box<dyn Error>> {
+    let library = Library::load(config).await?;
+
+    let mut game_ids = Vec::new();
+    for game in games {
+        game_ids.push(resolve_game_id(config, &library, game).await?);
+    }
+
+    Ok(game_ids)
+}
+
+/// Resolve a collection argument, a collection id or an `https://itch.io/c/<id>/<name>` URL.
+pub fn resolve_collection_id(collection: &str) -> Result<i64, Box<dyn Error>> {
+    let collection = collection.trim();
+
+    if let Ok(collection_id) = collection.parse::<i64>() {
+        return Ok(collection_id);
+    }
+
+    if let Ok(url) = reqwest::Url::parse(collection) {
+        if let Some(["c", id, ..]) = url.path_segments().map(|segments| segments.collect::<Vec<&str>>()).as_deref() {
+            if let Ok(collection_id) = id.parse::<i64>() {
+                return Ok(collection_id);
+            }
+        }
+    }
+
+    Err(Box::new(DownloadError::CollectionInvalid(collection.into())))
+}
+
+
+
+fn is_slug_part(part: &str) -> bool {
+    !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// If every char of `needle` appears in `haystack` in order.
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| haystack.any(|h| h == c))
+}
+
+
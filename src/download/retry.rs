@@ -0,0 +1,143 @@
+
+use std::{collections::hash_map::RandomState, error::Error, future::Future, hash::{BuildHasher, Hasher}, sync::Mutex, time::Duration};
+use console::style;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use super::{error::DownloadError, redact::redact};
+
+
+
+/// Retry, timeout & rate limit settings, the `network` config key.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Network {
+    /// Extra attempts for a failed API request or download.
+    pub retries: u32,
+    /// Seconds before an API request times out.
+    pub timeout: u64,
+    /// Seconds a download may go without receiving data before it is retried.
+    pub download_timeout: u64,
+    /// API requests per second, shared by everything running, `0` is unlimited.
+    pub requests_per_second: f64,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            timeout: 30,
+            download_timeout: 60,
+            requests_per_second: 4.0,
+        }
+    }
+}
+
+/// Outcome of a failed attempt.
+pub enum Attempt {
+    /// Worth trying again, after at least the given delay if there is one.
+    Retry(Box<dyn Error>, Option<Duration>),
+    Fail(Box<dyn Error>),
+}
+
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// When the next API request may be sent, shared by every request.
+static NEXT_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+
+
+/// Wait for a slot under the global request rate.
+pub async fn throttle(network: &Network) {
+    if network.requests_per_second <= 0.0 {
+        return;
+    }
+    let interval = Duration::from_secs_f64(1.0 / network.requests_per_second);
+
+    let slot = {
+        let mut next_request = NEXT_REQUEST.lock().unwrap();
+        let now = Instant::now();
+        let slot = next_request.map_or(now, |next| next.max(now));
+        *next_request = Some(slot + interval);
+        slot
+    };
+
+    tokio::time::sleep_until(slot).await;
+}
+
+/// Hold back every request until `delay` passed, after itch.io asked to slow down.
+fn pause(delay: Duration) {
+    let mut next_request = NEXT_REQUEST.lock().unwrap();
+    let until = Instant::now() + delay;
+    *next_request = Some(next_request.map_or(until, |next| next.max(until)));
+}
+
+/// Exponential backoff with jitter, between half & all of `BASE_DELAY * 2^attempt`.
+fn backoff(attempt: u32) -> Duration {
+    let delay = BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_DELAY);
+    let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    delay.mul_f64(0.5 + jitter * 0.5)
+}
+
+/// Whether a network error is worth retrying.
+pub fn classify(err: Box<dyn Error>) -> Attempt {
+    if let Some(download_error) = err.downcast_ref::<DownloadError>() {
+        return match download_error {
+            DownloadError::RateLimited(retry_after) => {
+                let delay = retry_after.map(Duration::from_secs);
+                Attempt::Retry(err, delay)
+            },
+            _ => Attempt::Fail(err),
+        };
+    }
+
+    if let Some(reqwest_error) = err.downcast_ref::<reqwest::Error>() {
+        return match reqwest_error.status() {
+            Some(status) if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() => Attempt::Retry(err, None),
+            Some(_) => Attempt::Fail(err),
+            None if reqwest_error.is_timeout() || reqwest_error.is_connect() || reqwest_error.is_request() || reqwest_error.is_body() => Attempt::Retry(err, None),
+            None => Attempt::Fail(err),
+        };
+    }
+
+    if let Some(io_error) = err.downcast_ref::<std::io::Error>() {
+        if matches!(io_error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::UnexpectedEof) {
+            return Attempt::Retry(err, None);
+        }
+    }
+
+    Attempt::Fail(err)
+}
+
+/// Run `f` until it succeeds, fails for good or runs out of retries.
+pub async fn retry<T, F, Fut>(network: &Network, what: &str, mut f: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Attempt>>,
+{
+    let mut attempt = 0;
+    loop {
+        let (err, min_delay) = match f().await {
+            Ok(value) => return Ok(value),
+            Err(Attempt::Fail(err)) => return Err(err),
+            Err(Attempt::Retry(err, _)) if attempt >= network.retries => return Err(err),
+            Err(Attempt::Retry(err, min_delay)) => (err, min_delay),
+        };
+
+        let delay = match min_delay {
+            Some(min_delay) => {
+                pause(min_delay);
+                min_delay.max(backoff(attempt))
+            },
+            None => backoff(attempt),
+        };
+        attempt += 1;
+
+        eprintln!("{} {} {}",
+            style(format!("{} failed ({}),", what, redact(err.to_string().trim_end_matches('.')))).yellow(),
+            style(format!("retrying in {:.1}s", delay.as_secs_f64())).yellow(),
+            style(format!("({}/{})", attempt, network.retries)).dim(),
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
@@ -1,5 +1,5 @@
 
-use std::{path::PathBuf, error::Error};
+use std::{path::{Path, PathBuf}, error::Error};
 use tokio::{fs, process::Command};
 
 use super::error::DownloadError;
@@ -9,11 +9,22 @@ use super::error::DownloadError;
 pub async fn extract_archive(archive: &PathBuf, out_dir: &PathBuf) -> Result<(), Box<dyn Error>> {
     fs::create_dir_all(out_dir).await?;
 
-    let result = Command::new("tar")
-        .arg("-x")
-        .arg("-f").arg(archive)
-        .arg("-C").arg(out_dir)
-        .output().await?;
+    // GNU tar can't read zip files, the bsdtar of windows & macOS can.
+    let is_zip = archive.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    let result = if cfg!(target_os = "linux") && is_zip {
+        Command::new("unzip")
+            .arg("-q")
+            .arg("-o")
+            .arg(archive)
+            .arg("-d").arg(out_dir)
+            .output().await?
+    } else {
+        Command::new("tar")
+            .arg("-x")
+            .arg("-f").arg(archive)
+            .arg("-C").arg(out_dir)
+            .output().await?
+    };
 
     if result.status.success() {
         Ok(())
@@ -24,3 +35,87 @@ pub async fn extract_archive(archive: &PathBuf, out_dir: &PathBuf) -> Result<(),
 }
 
 
+
+/// Total size in bytes of every file under `path`.
+pub async fn dir_size(path: &Path) -> Result<u64, Box<dyn Error>> {
+    let mut size = 0;
+    let mut queue: Vec<PathBuf> = vec![ path.to_path_buf() ];
+
+    while let Some(path) = queue.pop() {
+        let mut entries = fs::read_dir(&path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if meta.is_dir() {
+                queue.push(entry.path());
+            } else {
+                size += meta.len();
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+/// Move everything in `src` into `dst`, overwriting files that already exist, then remove `src`.
+pub async fn merge_dir(src: &Path, dst: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dst).await?;
+
+    let mut entries = fs::read_dir(src).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let target = dst.join(entry.file_name());
+        if entry.file_type().await?.is_dir() {
+            Box::pin(merge_dir(&entry.path(), &target)).await?;
+        } else {
+            fs::rename(entry.path(), &target).await?;
+        }
+    }
+
+    fs::remove_dir(src).await?;
+    Ok(())
+}
+
+/// Recursively copy `src` to `dst`.
+pub async fn copy_dir(src: &Path, dst: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dst).await?;
+
+    let mut entries = fs::read_dir(src).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let target = dst.join(entry.file_name());
+        if entry.file_type().await?.is_dir() {
+            Box::pin(copy_dir(&entry.path(), &target)).await?;
+        } else {
+            fs::copy(entry.path(), &target).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Move a directory, copying & deleting when `src` & `dst` are on different filesystems.
+///
+/// Copies go to `dst.partial` first, so an interrupted copy never leaves a half-copied `dst`.
+pub async fn move_dir(src: &Path, dst: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    match fs::rename(src, dst).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+            let mut partial = dst.as_os_str().to_owned();
+            partial.push(".partial");
+            let partial = PathBuf::from(partial);
+
+            if fs::try_exists(&partial).await? {
+                fs::remove_dir_all(&partial).await?;
+            }
+            copy_dir(src, &partial).await?;
+            fs::rename(&partial, dst).await?;
+            fs::remove_dir_all(src).await?;
+            Ok(())
+        },
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+
@@ -0,0 +1,110 @@
+
+use std::{error::Error, path::PathBuf};
+use console::style;
+use super::{api::ItchClient, config::Config, error::DownloadError, game::Game, library::{download_upload, Library}, manifest::{Manifest, VerifyReport}};
+
+
+
+fn print_report(report: &VerifyReport) {
+    for path in &report.missing {
+        println!("  {} {}", style("missing ").red(), path);
+    }
+    for path in &report.modified {
+        println!("  {} {}", style("modified").yellow(), path);
+    }
+    for path in &report.extra {
+        println!("  {} {}", style("extra   ").dim(), style(path).dim());
+    }
+}
+
+/// Re-download the installed upload & restore only the broken files.
+async fn repair_game(config: &Config, game: &Game, manifest: &Manifest, report: &VerifyReport) -> Result<VerifyReport, Box<dyn Error>> {
+    let client = ItchClient::new(config, game.profile.as_deref()).await?;
+    let game_uploads = client.game_uploads(&game.game_id, game.download_key_id).await?.uploads;
+    let game_upload = match game_uploads.iter().find(|game_upload| game_upload.id == manifest.upload_id) {
+        Some(game_upload) => game_upload,
+        None => return Err(Box::new(DownloadError::NoUpload(format!("installed upload {} is no longer available, run update instead", manifest.upload_id)))),
+    };
+
+    let mut staging_path = PathBuf::from(&config.games_dir);
+    staging_path.push("temp");
+    staging_path.push(format!("{}-repair", game.game_id));
+    download_upload(config, &client, game_upload, game.download_key_id, &staging_path).await?;
+
+    let game_path = game.path(config);
+    for path in report.broken() {
+        let source = staging_path.join(path);
+        if !tokio::fs::try_exists(&source).await? {
+            continue;
+        }
+        let target = game_path.join(path);
+        tokio::fs::create_dir_all(target.parent().unwrap()).await?;
+        tokio::fs::copy(&source, &target).await?;
+        println!("  {} {}", style("restored").green(), path);
+    }
+
+    tokio::fs::remove_dir_all(&staging_path).await?;
+
+    manifest.verify(&game_path).await
+}
+
+/// Verify installed games against their manifests, returns if every game is intact (after repairing).
+/// Games without a manifest can't be verified & count as not intact.
+pub async fn verify(config: &Config, game_ids: &[i64], all: bool, repair: bool) -> Result<bool, Box<dyn Error>> {
+    let mut library = Library::load(config).await?;
+
+    let games = if all {
+        library.games.clone()
+    } else {
+        let mut games = Vec::new();
+        for game_id in game_ids {
+            match library.get_game(config, game_id) {
+                Some(game) => games.push(game.clone()),
+                None => return Err(Box::new(DownloadError::GameNotInstalled(*game_id))),
+            }
+        }
+        games
+    };
+
+    let mut intact = true;
+    let mut unverified = 0;
+    for game in &games {
+        println!("{} {}", style("Verifying").magenta(), style(&game.title).magenta().bold());
+
+        let manifest = match Manifest::load(config, &game.game_id).await? {
+            Some(manifest) => manifest,
+            None => {
+                println!("  {}", style("No manifest, reinstall the game to create one").yellow());
+                unverified += 1;
+                intact = false;
+                continue;
+            },
+        };
+
+        let mut report = manifest.verify(&game.path(config)).await?;
+        print_report(&report);
+
+        if !report.is_ok() && repair {
+            println!("{}", style("Repairing").magenta());
+            report = repair_game(config, game, &manifest, &report).await?;
+            if !report.is_ok() {
+                println!("{}", style("Some files could not be restored, the upload changed since install").red());
+                print_report(&report);
+            }
+        }
+
+        if report.is_ok() {
+            println!("  {} {} files ok", style("ok").green(), manifest.files.len());
+        } else {
+            intact = false;
+        }
+    }
+
+    if unverified > 0 {
+        println!("{}", style(format!("{} game(s) have no manifest & were not verified", unverified)).yellow());
+    }
+
+    Ok(intact)
+}
+
+
@@ -1,25 +1,151 @@
 
 mod download;
-use std::error::Error;
-use clap::{Parser, Subcommand};
-use download::{config::Config, download_and_execute, select_and_play};
+use std::{error::Error, path::PathBuf};
+use clap::{ArgGroup, Parser, Subcommand};
+use console::style;
+use download::{config::Config, configure::{config_get, config_init, config_set, config_show}, info::show_info, jam::{jam_install, jam_list, jam_uninstall}, prompt::PromptMode, redact::redact, relocate::move_library, resolve::{resolve_collection_id, resolve_game, resolve_game_ids}, download_and_execute, game::LaunchOptions, install, install_collection, list_games, list_owned, select_and_play, set_launch_options, uninstall, update, verify::verify, ListSort, Platform};
 
 
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
+#[command(group(ArgGroup::new("prompt").args(["yes", "no", "no_input"])))]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Config file to use, instead of searching for one
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Config profile (itch.io account) to use
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Platform to install uploads for, instead of this computer's
+    #[arg(long, value_enum, global = true)]
+    platform: Option<Platform>,
+    /// Answer yes to every prompt
+    #[arg(long, short, global = true)]
+    yes: bool,
+    /// Answer no to every prompt
+    #[arg(long, global = true)]
+    no: bool,
+    /// Never prompt, fail if an answer is needed
+    #[arg(long, global = true)]
+    no_input: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Play a game
     Play {
+        /// Game id, itch.io URL, author/game or installed game title
         #[arg(index = 1)]
-        game_id: Option<i64>,
+        game: Option<String>,
+        /// Extra arguments passed to the game for this run, after `--`
+        #[arg(index = 2, last = true)]
+        args: Vec<String>,
+    },
+    /// Create, read or change the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Change settings of an installed game
+    Game {
+        #[command(subcommand)]
+        command: GameCommands,
+    },
+    /// Show itch.io info, install state & uploads of a game
+    Info {
+        /// Game id, itch.io URL, author/game or installed game title
+        #[arg(index = 1)]
+        game: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Download & install games without starting them
+    Install {
+        /// Game ids, itch.io URLs, author/game or installed game titles
+        #[arg(index = 1, required_unless_present = "collection")]
+        games: Vec<String>,
+        /// Install every game in a collection, by id or URL
+        #[arg(long, conflicts_with = "games")]
+        collection: Option<String>,
+        /// Remember the collection, so `update --all` installs games added to it
+        #[arg(long, requires = "collection")]
+        remember: bool,
+        /// Reinstall games that are already installed
+        #[arg(long)]
+        force: bool,
+        /// Ask which upload to install when several qualify, updates stay on the picked one
+        #[arg(long, conflicts_with = "collection")]
+        pick: bool,
+    },
+    /// Install, list or uninstall the entries of a game jam
+    Jam {
+        #[command(subcommand)]
+        command: JamCommands,
+    },
+    /// Manage the game library
+    Library {
+        #[command(subcommand)]
+        command: LibraryCommands,
+    },
+    /// List installed games
+    List {
+        /// Sort games by
+        #[arg(long, value_enum, default_value_t = ListSort::Title)]
+        sort: ListSort,
+        /// Only list games whose title contains this text
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only list entries of this jam
+        #[arg(long)]
+        jam: Option<String>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List games bought or claimed with the itch.io account & whether they are installed
+    Owned {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Uninstall a game & delete its files
+    Uninstall {
+        /// Game id, itch.io URL, author/game or installed game title
+        #[arg(index = 1)]
+        game: String,
+    },
+    /// Check installed games for updates & update them
+    ///
+    /// With `--all`, games added to remembered collections are installed too.
+    /// Exits with 0 when every game is current (or was updated),
+    /// 2 when updates are available with `--check`, & 1 on failure.
+    Update {
+        /// Game ids, itch.io URLs, author/game or installed game titles
+        #[arg(index = 1, required_unless_present = "all")]
+        games: Vec<String>,
+        /// Check every installed game
+        #[arg(long, conflicts_with = "games")]
+        all: bool,
+        /// Only check for updates, don't download them
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check installed files against the install manifest
+    Verify {
+        /// Game ids, itch.io URLs, author/game or installed game titles
+        #[arg(index = 1, required_unless_present = "all")]
+        games: Vec<String>,
+        /// Verify every installed game
+        #[arg(long, conflicts_with = "games")]
+        all: bool,
+        /// Re-download the upload & restore missing or modified files
+        #[arg(long)]
+        repair: bool,
     },
     #[command(hide = true)]
     Uri {
@@ -28,40 +154,238 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Create a config file, asking for the games directory & API key
+    Init {
+        /// Games directory, asked for if not given
+        #[arg(long)]
+        games_dir: Option<String>,
+        /// itch.io API key, asked for if not given
+        #[arg(long)]
+        api_key: Option<String>,
+    },
+    /// Print a config value
+    Get {
+        #[arg(index = 1)]
+        key: String,
+    },
+    /// Change a config value
+    Set {
+        #[arg(index = 1)]
+        key: String,
+        #[arg(index = 2)]
+        value: String,
+    },
+    /// Print the config in use, with the API key hidden
+    Show,
+}
 
+#[derive(Subcommand, Debug)]
+enum GameCommands {
+    /// Change how a game is started
+    Set {
+        /// Game id, itch.io URL, author/game or installed game title
+        #[arg(index = 1)]
+        game: String,
+        /// Executable to start, relative to the game directory (empty to find it automatically)
+        #[arg(long)]
+        exe: Option<String>,
+        /// Argument passed to the game, replaces the current arguments (repeatable)
+        #[arg(long = "arg", allow_hyphen_values = true)]
+        args: Vec<String>,
+        /// Environment variable as KEY=VALUE, KEY= removes it (repeatable)
+        #[arg(long = "env", value_parser = parse_env)]
+        env: Vec<(String, String)>,
+        /// Working directory, relative to the game directory (empty for the default)
+        #[arg(long)]
+        cwd: Option<String>,
+        /// Reset all launch options before applying the changes
+        #[arg(long)]
+        clear: bool,
+    },
+}
 
-async fn play(game_id: i64) -> Result<(), Box<dyn Error>> {
-    download_and_execute(&Config::load().await?, game_id).await?;
-    Ok(())
+fn parse_env(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err(format!("expected KEY=VALUE, got \"{}\"", value)),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum JamCommands {
+    /// Install every entry of a jam that has a build for this platform
+    Install {
+        /// Jam URL, like https://itch.io/jam/name
+        #[arg(index = 1)]
+        jam: String,
+        /// Reinstall entries that are already installed
+        #[arg(long)]
+        force: bool,
+    },
+    /// List jams with installed entries
+    List,
+    /// Uninstall every installed entry of a jam
+    Uninstall {
+        /// Jam name, as shown by `jam list`
+        #[arg(index = 1)]
+        jam: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LibraryCommands {
+    /// Move every game to a different games directory
+    Move {
+        #[arg(index = 1)]
+        new_dir: PathBuf,
+        /// Only show what would be moved & the space needed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+
+
+fn prompt_mode(args: &Cli) -> Option<PromptMode> {
+    if args.yes {
+        Some(PromptMode::Yes)
+    } else if args.no {
+        Some(PromptMode::No)
+    } else if args.no_input {
+        Some(PromptMode::NoInput)
+    } else {
+        None
+    }
+}
+
+async fn load_config(args: &Cli) -> Result<Config, Box<dyn Error>> {
+    let mut config = Config::load(args.config.as_deref()).await?;
+    config.select_profile(args.profile.as_deref())?;
+    config.platform = args.platform;
+
+    if let Some(prompt) = prompt_mode(args) {
+        config.prompt = prompt;
+    }
+
+    Ok(config)
 }
 
-async fn select_play() -> Result<(), Box<dyn Error>> {
-    select_and_play(&Config::load().await?).await?;
+async fn play(args: &Cli, game: &str, extra_args: &[String]) -> Result<(), Box<dyn Error>> {
+    let config = load_config(args).await?;
+    let game_id = resolve_game(&config, game).await?;
+    download_and_execute(&config, game_id, extra_args).await?;
     Ok(())
 }
 
+async fn select_play(args: &Cli) -> Result<(), Box<dyn Error>> {
+    select_and_play(&load_config(args).await?).await?;
+    Ok(())
+}
 
 
 
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Cli::parse();
 
+async fn run(args: &Cli) -> Result<(), Box<dyn Error>> {
     match &args.command {
-        None => select_play().await?,
+        None => select_play(args).await?,
 
-        Some(Commands::Play { game_id }) => {
-            match game_id {
-                Some(game_id) => play(game_id.clone()).await?,
-                None => select_play().await?,
+        Some(Commands::Play { game: Some(game), args: extra_args }) => play(args, game, extra_args).await?,
+        Some(Commands::Play { game: None, .. }) => select_play(args).await?,
+
+        Some(Commands::Config { command }) => {
+            match command {
+                ConfigCommands::Init { games_dir, api_key } => {
+                    let mode = prompt_mode(args).unwrap_or_default();
+                    config_init(args.config.as_deref(), mode, games_dir.clone(), api_key.clone()).await?;
+                },
+                ConfigCommands::Get { key } => config_get(args.config.as_deref(), key).await?,
+                ConfigCommands::Set { key, value } => config_set(args.config.as_deref(), key, value).await?,
+                ConfigCommands::Show => config_show(&load_config(args).await?).await?,
+            }
+        },
+
+        Some(Commands::Game { command: GameCommands::Set { game, exe, args: launch_args, env, cwd, clear } }) => {
+            let config = load_config(args).await?;
+            let game_id = resolve_game(&config, game).await?;
+            let changes = LaunchOptions {
+                exe: exe.clone(),
+                args: launch_args.clone(),
+                env: env.iter().cloned().collect(),
+                cwd: cwd.clone(),
+            };
+            set_launch_options(&config, game_id, changes, *clear).await?;
+        },
+
+        Some(Commands::Info { game, json }) => {
+            let config = load_config(args).await?;
+            let game_id = resolve_game(&config, game).await?;
+            show_info(&config, game_id, *json).await?;
+        },
+
+        Some(Commands::Install { games, collection, remember, force, pick }) => {
+            let config = load_config(args).await?;
+            let installed = match collection {
+                Some(collection) => install_collection(&config, resolve_collection_id(collection)?, *force, *remember).await?,
+                None => install(&config, &resolve_game_ids(&config, games).await?, *force, *pick).await?,
+            };
+            if !installed {
+                std::process::exit(1);
+            }
+        },
+
+        Some(Commands::Jam { command }) => {
+            let config = load_config(args).await?;
+            match command {
+                JamCommands::Install { jam, force } => {
+                    if !jam_install(&config, jam, *force).await? {
+                        std::process::exit(1);
+                    }
+                },
+                JamCommands::List => jam_list(&config).await?,
+                JamCommands::Uninstall { jam } => jam_uninstall(&config, jam).await?,
+            }
+        },
+
+        Some(Commands::Library { command: LibraryCommands::Move { new_dir, dry_run } }) => {
+            move_library(&load_config(args).await?, new_dir, *dry_run).await?;
+        },
+
+        Some(Commands::List { sort, filter, jam, json }) => {
+            list_games(&load_config(args).await?, *sort, filter.as_deref(), jam.as_deref(), *json).await?;
+        },
+
+        Some(Commands::Owned { json }) => {
+            list_owned(&load_config(args).await?, *json).await?;
+        },
+
+        Some(Commands::Uninstall { game }) => {
+            let config = load_config(args).await?;
+            let game_id = resolve_game(&config, game).await?;
+            uninstall(&config, game_id).await?;
+        },
+
+        Some(Commands::Update { games, all, check }) => {
+            let config = load_config(args).await?;
+            let game_ids = resolve_game_ids(&config, games).await?;
+            let outcome = update(&config, &game_ids, *all, *check).await?;
+            std::process::exit(outcome.exit_code());
+        },
+
+        Some(Commands::Verify { games, all, repair }) => {
+            let config = load_config(args).await?;
+            let game_ids = resolve_game_ids(&config, games).await?;
+            if !verify(&config, &game_ids, *all, *repair).await? {
+                std::process::exit(1);
             }
         },
 
         Some(Commands::Uri { uri }) => {
             match uri.split("/").filter(|s| !s.is_empty()).collect::<Vec<&str>>()[..] {
-                ["itch-io-downloader:", "play", id] => play(id.parse()?).await?,
-                ["itch-io-downloader:", "play"] => select_play().await?,
+                ["itch-io-downloader:", "play", id] => play(args, id, &[]).await?,
+                ["itch-io-downloader:", "play"] => select_play(args).await?,
                 _ => panic!("Invalid URI."),
             }
         }
@@ -70,4 +394,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::main]
+async fn main() {
+    let args = Cli::parse();
+
+    if let Err(err) = run(&args).await {
+        eprintln!("{} {}", style("Error:").red().bold(), style(redact(&err.to_string())).red());
+        std::process::exit(1);
+    }
+}
+
 